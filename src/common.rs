@@ -17,8 +17,12 @@
 
 //! Commons utilities and traits
 
-use crate::core::AppInput;
-use serde::{Deserialize, Serialize};
+use crate::{
+    core::{AppInput, AppOutput, PublicKey},
+    hash::{Hash, HashAlgorithm},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_value::{DeserializerError, Value};
 use std::fmt::Display;
 
 /// Wasm application execution context.
@@ -49,6 +53,11 @@ impl WasmError {
     pub fn new(msg: &str) -> WasmError {
         WasmError(msg.to_owned())
     }
+
+    /// Reconstruct the error from a failed application output.
+    pub fn from_app_output(output: &AppOutput) -> WasmError {
+        WasmError::new(&String::from_utf8_lossy(output.data))
+    }
 }
 
 /// Compact representation of a wasm slice components.
@@ -121,6 +130,11 @@ pub(crate) fn slice_from_wslice<'a>(wslice: WasmSlice) -> &'a [u8] {
     unsafe { std::slice::from_raw_parts(addr as *mut u8, length as usize) }
 }
 
+/// Convert bytes to a string slice, failing if not valid UTF-8.
+pub fn str_from_bytes(buf: &[u8]) -> WasmResult<&str> {
+    std::str::from_utf8(buf).map_err(|_err| WasmError::new("invalid utf-8 string"))
+}
+
 /// Serialize a type implementing `Serialize` trait using MessagePack format with named keys.
 pub fn rmp_serialize_named<T>(val: &T) -> WasmResult<Vec<u8>>
 where
@@ -145,6 +159,139 @@ where
     rmp_serde::from_slice(buf).map_err(|_err| WasmError::new("deserialization failure"))
 }
 
+/// Deserialize a MessagePack buffer tolerating fields unknown to the target type.
+///
+/// When the type is encoded with named fields (map), unknown fields are simply
+/// ignored. When it is encoded positionally (array), the extra trailing
+/// elements are dropped, this assumes that newer versions of the type only
+/// append fields at the end. Only a length mismatch drops elements, any other
+/// error (e.g. a wrongly typed element) is reported.
+pub fn rmp_deserialize_lenient<T: DeserializeOwned>(buf: &[u8]) -> WasmResult<T> {
+    let value: Value = rmp_deserialize(buf)?;
+    match value {
+        Value::Seq(mut seq) => loop {
+            match Value::Seq(seq.clone()).deserialize_into() {
+                Ok(res) => return Ok(res),
+                Err(DeserializerError::InvalidLength(..)) if !seq.is_empty() => {
+                    seq.pop();
+                }
+                Err(_err) => return Err(WasmError::new("deserialization failure")),
+            }
+        },
+        value => value
+            .deserialize_into()
+            .map_err(|_err| WasmError::new("deserialization failure")),
+    }
+}
+
+/// Deserialize a list of items validating each of them.
+///
+/// On failure the error message reports the index of the first invalid item.
+pub fn validate_list<T: DeserializeOwned>(
+    buf: &[u8],
+    validate: impl Fn(&T) -> WasmResult<()>,
+) -> WasmResult<Vec<T>> {
+    let items: Vec<T> = rmp_deserialize(buf)?;
+    for (index, item) in items.iter().enumerate() {
+        validate(item)
+            .map_err(|err| WasmError::new(&format!("invalid item {}: {}", index, err)))?;
+    }
+    Ok(items)
+}
+
+/// Response tagged with the identifier of its data schema.
+///
+/// Clients can check the `schema` before parsing the `data`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SchemaEnvelope<T> {
+    pub schema: String,
+    pub data: T,
+}
+
+/// Serialize the value within a `{schema, data}` map, see `SchemaEnvelope`.
+pub fn respond_with_schema<T: Serialize>(val: &T, schema_id: &str) -> WasmResult<Vec<u8>> {
+    #[derive(Serialize)]
+    struct EnvelopeRef<'a, T> {
+        schema: &'a str,
+        data: &'a T,
+    }
+    rmp_serialize_named(&EnvelopeRef {
+        schema: schema_id,
+        data: val,
+    })
+}
+
+/// Check that a MessagePack buffer doesn't exceed the given nesting depth.
+///
+/// The buffer is scanned without deserializing it, thus it is safe to use
+/// against inputs crafted to exhaust the stack during deserialization.
+/// Fails with `malformed input` if the depth is exceeded or if the buffer is
+/// not valid MessagePack.
+pub fn check_args_depth(buf: &[u8], max_depth: usize) -> WasmResult<()> {
+    fn malformed() -> WasmError {
+        WasmError::new("malformed input")
+    }
+
+    fn read_len(buf: &[u8], off: &mut usize, size: usize) -> WasmResult<u64> {
+        let bytes = buf.get(*off..*off + size).ok_or_else(malformed)?;
+        *off += size;
+        Ok(bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    // Number of items still to be read for each open container.
+    let mut pending: Vec<u64> = vec![1];
+    let mut off = 0;
+    while let Some(remaining) = pending.last_mut() {
+        if *remaining == 0 {
+            pending.pop();
+            continue;
+        }
+        *remaining -= 1;
+
+        let marker = *buf.get(off).ok_or_else(malformed)?;
+        off += 1;
+        let (skip, items) = match marker {
+            0x00..=0x7f | 0xc0 | 0xc2 | 0xc3 | 0xe0..=0xff => (0, None),
+            0x80..=0x8f => (0, Some(2 * (marker & 0x0f) as u64)),
+            0x90..=0x9f => (0, Some((marker & 0x0f) as u64)),
+            0xa0..=0xbf => ((marker & 0x1f) as u64, None),
+            0xc4 | 0xd9 => (read_len(buf, &mut off, 1)?, None),
+            0xc5 | 0xda => (read_len(buf, &mut off, 2)?, None),
+            0xc6 | 0xdb => (read_len(buf, &mut off, 4)?, None),
+            0xc7 => (read_len(buf, &mut off, 1)? + 1, None),
+            0xc8 => (read_len(buf, &mut off, 2)? + 1, None),
+            0xc9 => (read_len(buf, &mut off, 4)? + 1, None),
+            0xcc | 0xd0 => (1, None),
+            0xcd | 0xd1 => (2, None),
+            0xca | 0xce | 0xd2 => (4, None),
+            0xcb | 0xcf | 0xd3 => (8, None),
+            0xd4 => (2, None),
+            0xd5 => (3, None),
+            0xd6 => (5, None),
+            0xd7 => (9, None),
+            0xd8 => (17, None),
+            0xdc => (0, Some(read_len(buf, &mut off, 2)?)),
+            0xdd => (0, Some(read_len(buf, &mut off, 4)?)),
+            0xde => (0, Some(2 * read_len(buf, &mut off, 2)?)),
+            0xdf => (0, Some(2 * read_len(buf, &mut off, 4)?)),
+            _ => return Err(malformed()),
+        };
+        let skip = usize::try_from(skip).map_err(|_err| malformed())?;
+        off = off.checked_add(skip).ok_or_else(malformed)?;
+        if off > buf.len() {
+            return Err(malformed());
+        }
+        if let Some(items) = items {
+            // The first entry is the root value, not a container.
+            if pending.len() > max_depth {
+                return Err(malformed());
+            }
+            pending.push(items);
+        }
+    }
+    Ok(())
+}
+
 /// Tool to divide a number by handling the reminder.
 /// It returns a vector with the resultant outcome for each division.
 /// In case of reminder, it's given to the first division result.
@@ -169,6 +316,218 @@ pub fn divide(number: u64, dividers: &[u64]) -> WasmResult<Vec<u64>> {
     Ok(result)
 }
 
+/// Split `total` proportionally to the `weights` using the largest-remainder method.
+///
+/// Each recipient gets the floor of its share, the remaining units are then
+/// given one each to the recipients with the largest fractional part (ties go
+/// to the first ones). The parts always sum to `total`.
+pub fn distribute(total: u64, weights: &[u64]) -> WasmResult<Vec<u64>> {
+    let weights_sum: u128 = weights.iter().map(|weight| *weight as u128).sum();
+    if weights_sum == 0 {
+        return Err(WasmError::new("nothing to distribute"));
+    }
+    let (mut parts, remainders): (Vec<u64>, Vec<u128>) = weights
+        .iter()
+        .map(|weight| {
+            let share = total as u128 * *weight as u128;
+            ((share / weights_sum) as u64, share % weights_sum)
+        })
+        .unzip();
+
+    let assigned: u64 = parts.iter().sum();
+    let mut order: Vec<usize> = (0..parts.len()).collect();
+    order.sort_by(|a, b| remainders[*b].cmp(&remainders[*a]));
+    order
+        .into_iter()
+        .take((total - assigned) as usize)
+        .for_each(|index| parts[index] += 1);
+
+    Ok(parts)
+}
+
+/// Check that a string argument is one of the allowed values.
+///
+/// On failure the error message lists the allowed values.
+pub fn require_one_of<'a>(value: &'a str, allowed: &[&str]) -> WasmResult<&'a str> {
+    match allowed.contains(&value) {
+        true => Ok(value),
+        false => Err(WasmError::new(&format!(
+            "invalid value `{}`, expected one of: {}",
+            value,
+            allowed.join(", ")
+        ))),
+    }
+}
+
+/// Check that a timestamp argument is in the future with respect to `now`.
+///
+/// The `now` value is usually the block timestamp given by `get_block_time`.
+pub fn require_future_timestamp(ts: u64, now: u64) -> WasmResult<u64> {
+    match ts > now {
+        true => Ok(ts),
+        false => Err(WasmError::new("timestamp not in the future")),
+    }
+}
+
+/// Check that `now` is strictly before the `deadline`.
+///
+/// The `now` value is usually the block timestamp given by `get_block_time`.
+pub fn require_before(deadline: u64, now: u64) -> WasmResult<()> {
+    match now < deadline {
+        true => Ok(()),
+        false => Err(WasmError::new("deadline expired")),
+    }
+}
+
+/// Check that `now` is not before the `start`.
+///
+/// The `now` value is usually the block timestamp given by `get_block_time`.
+pub fn require_after(start: u64, now: u64) -> WasmResult<()> {
+    match now >= start {
+        true => Ok(()),
+        false => Err(WasmError::new("not yet started")),
+    }
+}
+
+/// Check that a duration argument, in seconds, doesn't exceed `max`.
+pub fn require_duration_max(secs: u64, max: u64) -> WasmResult<u64> {
+    match secs <= max {
+        true => Ok(secs),
+        false => Err(WasmError::new("duration exceeds maximum")),
+    }
+}
+
+/// Check that a memo (e.g. the transfer accessory data) doesn't exceed `max` bytes.
+pub fn require_memo_size(data: &[u8], max: usize) -> WasmResult<()> {
+    match data.len() <= max {
+        true => Ok(()),
+        false => Err(WasmError::new("memo too large")),
+    }
+}
+
+/// Convert a byte slice into a fixed size array.
+///
+/// Fails if the slice length is not exactly `N`.
+pub fn fixed_bytes<const N: usize>(buf: &[u8]) -> WasmResult<[u8; N]> {
+    <[u8; N]>::try_from(buf).map_err(|_err| WasmError::new("bad bytes length"))
+}
+
+/// Convert a byte slice into a fixed size array, left-padding with zeros
+/// when the slice is shorter than `N`.
+///
+/// Fails if the slice is longer than `N`.
+pub fn fixed_bytes_padded<const N: usize>(buf: &[u8]) -> WasmResult<[u8; N]> {
+    if buf.len() > N {
+        return Err(WasmError::new("bad bytes length"));
+    }
+    let mut res = [0u8; N];
+    res[N - buf.len()..].copy_from_slice(buf);
+    Ok(res)
+}
+
+/// Derive a deterministic account id from a `base` account and a `salt`.
+///
+/// The id is the base58 encoding of the SHA-256 multihash of `base || salt`,
+/// i.e. the same format used by the core for account ids.
+///
+/// No key pair is behind the derived account, thus nobody can sign for it.
+/// The account only exists on-chain once some data or asset is stored into it
+/// and it is meant to be managed exclusively by the contract logic (e.g. as an
+/// escrow).
+pub fn derive_subaccount(base: &str, salt: &[u8]) -> String {
+    let mut data = base.as_bytes().to_vec();
+    data.extend_from_slice(salt);
+    account_id_from_data(&data)
+}
+
+/// Base58 alphabet used by the core to encode the account ids.
+pub const ACCOUNT_ID_ALPHABET: &bs58::Alphabet = bs58::Alphabet::BITCOIN;
+
+/// Base58 encoding of the SHA-256 multihash of the data.
+pub(crate) fn account_id_from_data(data: &[u8]) -> String {
+    let hash = Hash::from_data(HashAlgorithm::Sha256, data);
+    bs58::encode(hash.as_bytes())
+        .with_alphabet(ACCOUNT_ID_ALPHABET)
+        .into_string()
+}
+
+/// Command signed by the owner of the embedded public key.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SignedCommand<T> {
+    /// Command payload.
+    pub payload: T,
+    /// Public key of the signer.
+    pub public_key: PublicKey,
+    /// Signature of the MessagePack serialized payload.
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl<T: Serialize> SignedCommand<T> {
+    /// Verify the signature over the serialized payload and, on success,
+    /// return the payload.
+    pub fn verify_and_extract(self) -> WasmResult<T> {
+        let data = rmp_serialize(&self.payload)?;
+        match crate::host_wrap::verify(&self.public_key, &data, &self.signature) {
+            true => Ok(self.payload),
+            false => Err(WasmError::new("invalid signature")),
+        }
+    }
+}
+
+/// Method response envelope for operations that may not complete immediately.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum Outcome<T> {
+    /// Operation completed with the given result.
+    Completed(T),
+    /// Operation accepted but not yet completed.
+    Pending { reason: String },
+    /// Operation refused.
+    Rejected { reason: String },
+}
+
+/// Smart contract metadata returned by the `__meta` method, see `meta_method!`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ContractMeta {
+    pub name: String,
+    pub version: String,
+    pub methods: Vec<String>,
+}
+
+/// Description of a method exported by a smart contract, see `abi!`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DispatchEntry {
+    pub method: String,
+    pub doc: String,
+}
+
+/// Network specific parameters.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct NetworkParams {
+    /// Account id of the asset used to pay the fuel.
+    pub fuel_asset: String,
+}
+
+/// Fuel asset of the networks without specific parameters.
+pub const DEFAULT_FUEL_ASSET: &str = "TRINCI";
+
+/// Get the parameters of the given network.
+///
+/// Networks without specific parameters (e.g. "skynet") get the default ones.
+/// When not running in wasm, custom parameters can be registered via
+/// `not_wasm::set_network_params`.
+pub fn network_params(network: &str) -> NetworkParams {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(params) = crate::not_wasm::get_network_params(network) {
+        return params;
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = network;
+    NetworkParams {
+        fuel_asset: DEFAULT_FUEL_ASSET.to_owned(),
+    }
+}
+
 /// Value that has been already packed, thus it doesn't require further
 /// processing and shall be taken "as-is".
 #[derive(Default, Debug)]
@@ -218,7 +577,20 @@ impl Deserializable<'_> for PackedValue {
 
 #[cfg(test)]
 mod tests {
-    use crate::divide;
+    use crate::{
+        check_args_depth,
+        core::{AppOutput, PublicKey},
+        derive_subaccount, distribute, divide,
+        ecdsa::{self, CurveId},
+        fixed_bytes, fixed_bytes_padded, network_params, require_after, require_before,
+        require_duration_max, require_future_timestamp, require_memo_size, require_one_of,
+        respond_with_schema, rmp_deserialize, rmp_deserialize_lenient, rmp_serialize,
+        rmp_serialize_named, str_from_bytes, validate_list, value, Outcome, SchemaEnvelope,
+        SignedCommand, Value, WasmError, DEFAULT_FUEL_ASSET,
+    };
+    use serde::{Deserialize, Serialize};
+
+    const BASE: &str = "QmYHnEQLdf5h7KYbjFPuHSRk2SPgdXrJWFh5W696HPfq7i";
 
     #[test]
     fn test_divide_empty_dividers() {
@@ -229,6 +601,44 @@ mod tests {
         assert_eq!(err.to_string(), "nothing to divide");
     }
 
+    #[test]
+    fn test_distribute_indivisible_remainder() {
+        let parts = distribute(100, &[1, 1, 1]).unwrap();
+
+        assert_eq!(parts, vec![34, 33, 33]);
+    }
+
+    #[test]
+    fn test_distribute_largest_remainder() {
+        let parts = distribute(10, &[3, 3, 4]).unwrap();
+        let uneven = distribute(7, &[1, 2, 4]).unwrap();
+
+        assert_eq!(parts, vec![3, 3, 4]);
+        assert_eq!(uneven, vec![1, 2, 4]);
+        assert_eq!(distribute(5, &[2, 3, 5]).unwrap(), vec![1, 2, 2]);
+    }
+
+    #[test]
+    fn test_distribute_zero_weights() {
+        let parts = distribute(11, &[0, 5, 0, 5]).unwrap();
+        let err = distribute(11, &[0, 0]).unwrap_err();
+
+        assert_eq!(parts, vec![0, 6, 0, 5]);
+        assert_eq!(err.to_string(), "nothing to distribute");
+    }
+
+    #[test]
+    fn test_distribute_sums_to_total() {
+        let weights = [7, 13, 1, 0, 29, 3];
+
+        for total in [0, 1, 53, 999, u64::MAX] {
+            let parts = distribute(total, &weights).unwrap();
+
+            let sum: u128 = parts.iter().map(|part| *part as u128).sum();
+            assert_eq!(sum, total as u128);
+        }
+    }
+
     #[test]
     fn test_divide_zero() {
         let dividers: Vec<u64> = vec![2, 4];
@@ -271,4 +681,403 @@ mod tests {
 
         assert_eq!(res.iter().fold(0, |acc, &val| acc + val), value);
     }
+
+    #[test]
+    fn test_derive_subaccount_deterministic() {
+        let id1 = derive_subaccount(BASE, b"escrow");
+        let id2 = derive_subaccount(BASE, b"escrow");
+
+        assert_eq!(id1, id2);
+        assert!(id1.starts_with("Qm"));
+    }
+
+    #[test]
+    fn test_derive_subaccount_different_salts() {
+        let id1 = derive_subaccount(BASE, b"escrow-1");
+        let id2 = derive_subaccount(BASE, b"escrow-2");
+
+        assert_ne!(id1, id2);
+    }
+
+    fn create_signed_command(signature: Vec<u8>) -> SignedCommand<String> {
+        SignedCommand {
+            payload: "withdraw".to_string(),
+            public_key: PublicKey::Ecdsa(ecdsa::PublicKey {
+                curve_id: CurveId::Secp384R1,
+                value: vec![4, 1, 2, 3],
+            }),
+            signature,
+        }
+    }
+
+    // The mocked `verify` uses the first signature byte as result.
+
+    #[test]
+    fn test_signed_command_valid_signature() {
+        let cmd = create_signed_command(vec![1, 0xab, 0xcd]);
+
+        let payload = cmd.verify_and_extract().unwrap();
+
+        assert_eq!(payload, "withdraw");
+    }
+
+    #[test]
+    fn test_signed_command_invalid_signature() {
+        let cmd = create_signed_command(vec![0, 0xab, 0xcd]);
+
+        let err = cmd.verify_and_extract().unwrap_err();
+
+        assert_eq!(err.to_string(), "invalid signature");
+    }
+
+    #[test]
+    fn test_fixed_bytes() {
+        let exact = fixed_bytes::<4>(&[1, 2, 3, 4]).unwrap();
+        let short = fixed_bytes::<4>(&[1, 2, 3]).unwrap_err();
+        let long = fixed_bytes::<4>(&[1, 2, 3, 4, 5]).unwrap_err();
+
+        assert_eq!(exact, [1, 2, 3, 4]);
+        assert_eq!(short.to_string(), "bad bytes length");
+        assert_eq!(long.to_string(), "bad bytes length");
+    }
+
+    #[test]
+    fn test_fixed_bytes_padded() {
+        let exact = fixed_bytes_padded::<4>(&[1, 2, 3, 4]).unwrap();
+        let short = fixed_bytes_padded::<4>(&[1, 2]).unwrap();
+        let long = fixed_bytes_padded::<4>(&[1, 2, 3, 4, 5]).unwrap_err();
+
+        assert_eq!(exact, [1, 2, 3, 4]);
+        assert_eq!(short, [0, 0, 1, 2]);
+        assert_eq!(long.to_string(), "bad bytes length");
+    }
+
+    #[test]
+    fn test_require_one_of_accepted() {
+        let side = require_one_of("sell", &["buy", "sell"]).unwrap();
+
+        assert_eq!(side, "sell");
+    }
+
+    #[test]
+    fn test_require_one_of_rejected() {
+        let err = require_one_of("hold", &["buy", "sell"]).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "invalid value `hold`, expected one of: buy, sell"
+        );
+    }
+
+    #[test]
+    fn test_require_future_timestamp() {
+        let now = 1652780598;
+
+        assert_eq!(require_future_timestamp(now + 60, now).unwrap(), now + 60);
+        assert_eq!(
+            require_future_timestamp(now - 60, now)
+                .unwrap_err()
+                .to_string(),
+            "timestamp not in the future"
+        );
+    }
+
+    #[test]
+    fn test_require_before() {
+        let deadline = 1652780598;
+
+        assert!(require_before(deadline, deadline - 1).is_ok());
+        assert_eq!(
+            require_before(deadline, deadline).unwrap_err().to_string(),
+            "deadline expired"
+        );
+    }
+
+    #[test]
+    fn test_require_after() {
+        let start = 1652780598;
+
+        assert!(require_after(start, start).is_ok());
+        assert_eq!(
+            require_after(start, start - 1).unwrap_err().to_string(),
+            "not yet started"
+        );
+    }
+
+    #[test]
+    fn test_time_window() {
+        let (start, deadline) = (1652780598, 1652780658);
+        let in_window = |now| require_after(start, now).and(require_before(deadline, now));
+
+        assert!(in_window(start).is_ok());
+        assert!(in_window(deadline - 1).is_ok());
+        assert!(in_window(start - 1).is_err());
+        assert!(in_window(deadline).is_err());
+    }
+
+    #[test]
+    fn test_require_duration_max() {
+        assert_eq!(require_duration_max(3600, 3600).unwrap(), 3600);
+        assert_eq!(
+            require_duration_max(3601, 3600).unwrap_err().to_string(),
+            "duration exceeds maximum"
+        );
+    }
+
+    #[test]
+    fn test_network_params_default() {
+        let params = network_params("skynet");
+
+        assert_eq!(params.fuel_asset, DEFAULT_FUEL_ASSET);
+    }
+
+    #[test]
+    fn test_str_from_bytes() {
+        assert_eq!(str_from_bytes("hello".as_bytes()).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_str_from_invalid_bytes() {
+        let err = str_from_bytes(&[240, 159, 146]).unwrap_err();
+
+        assert_eq!(err.to_string(), "invalid utf-8 string");
+    }
+
+    #[test]
+    fn test_require_memo_size() {
+        assert!(require_memo_size(&[0; 4], 4).is_ok());
+        assert_eq!(
+            require_memo_size(&[0; 5], 4).unwrap_err().to_string(),
+            "memo too large"
+        );
+    }
+
+    #[test]
+    fn test_error_from_app_output() {
+        let buf = hex::decode("92c2c4086261642061726773").unwrap();
+        let output: AppOutput = rmp_deserialize(&buf).unwrap();
+
+        let err = WasmError::from_app_output(&output);
+
+        assert!(!output.success);
+        assert_eq!(err.to_string(), "bad args");
+    }
+
+    #[test]
+    fn test_outcome_round_trip() {
+        let outcomes = vec![
+            Outcome::Completed(42u64),
+            Outcome::Pending {
+                reason: "waiting for approvals".to_string(),
+            },
+            Outcome::Rejected {
+                reason: "expired".to_string(),
+            },
+        ];
+
+        for outcome in outcomes {
+            let buf = rmp_serialize_named(&outcome).unwrap();
+            let res: Outcome<u64> = rmp_deserialize(&buf).unwrap();
+
+            assert_eq!(res, outcome);
+        }
+    }
+
+    fn create_nested_args(depth: usize) -> Vec<u8> {
+        let mut buf = vec![0x91; depth];
+        buf.push(0xc0);
+        buf
+    }
+
+    #[test]
+    fn test_check_args_depth() {
+        let buf = create_nested_args(32);
+
+        assert!(check_args_depth(&buf, 32).is_ok());
+        assert_eq!(
+            check_args_depth(&buf, 31).unwrap_err().to_string(),
+            "malformed input"
+        );
+    }
+
+    #[test]
+    fn test_check_args_depth_map() {
+        // {"a": [1, {"b": 2}], "c": "d"}
+        let buf = hex::decode("82a161920181a16202a163a164").unwrap();
+
+        assert!(check_args_depth(&buf, 3).is_ok());
+        assert!(check_args_depth(&buf, 2).is_err());
+    }
+
+    #[test]
+    fn test_check_args_depth_malformed() {
+        let mut buf = create_nested_args(4);
+        buf.pop();
+
+        assert!(check_args_depth(&buf, 32).is_err());
+        assert!(check_args_depth(&[0xc1], 32).is_err());
+        assert!(check_args_depth(&[0xa5, 0x41], 32).is_err());
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Args {
+        units: u64,
+        memo: String,
+    }
+
+    #[derive(Serialize)]
+    struct ExtendedArgs {
+        units: u64,
+        memo: String,
+        extra: bool,
+    }
+
+    fn create_extended_args() -> ExtendedArgs {
+        ExtendedArgs {
+            units: 42,
+            memo: "hello".to_string(),
+            extra: true,
+        }
+    }
+
+    #[test]
+    fn test_deserialize_lenient_named() {
+        let buf = rmp_serialize_named(&create_extended_args()).unwrap();
+
+        let args: Args = rmp_deserialize_lenient(&buf).unwrap();
+
+        assert_eq!(
+            args,
+            Args {
+                units: 42,
+                memo: "hello".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_lenient_positional() {
+        let buf = rmp_serialize(&create_extended_args()).unwrap();
+
+        let args: Args = rmp_deserialize_lenient(&buf).unwrap();
+
+        assert_eq!(
+            args,
+            Args {
+                units: 42,
+                memo: "hello".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_lenient_bad_type() {
+        let buf = rmp_serialize(&(42u64, 3u8)).unwrap();
+
+        let res = rmp_deserialize_lenient::<Args>(&buf);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_lenient_positional_bad_element() {
+        let buf = rmp_serialize(&(1u64, 2u64, "x")).unwrap();
+
+        let res = rmp_deserialize_lenient::<Vec<u64>>(&buf);
+
+        assert!(res.is_err());
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct DefaultedArgs {
+        units: u64,
+        #[serde(default)]
+        extra: u64,
+    }
+
+    #[test]
+    fn test_deserialize_lenient_positional_bad_defaulted_element() {
+        let buf = rmp_serialize(&(42u64, "x")).unwrap();
+
+        let res = rmp_deserialize_lenient::<DefaultedArgs>(&buf);
+
+        assert!(res.is_err());
+    }
+
+    fn check_not_zero(item: &u64) -> WasmResult<()> {
+        match *item {
+            0 => Err(WasmError::new("zero value")),
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn test_validate_list() {
+        let buf = rmp_serialize(&vec![1u64, 2, 3]).unwrap();
+
+        let items = validate_list(&buf, check_not_zero).unwrap();
+
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_validate_list_invalid_item() {
+        let buf = rmp_serialize(&vec![1u64, 2, 0, 4, 0]).unwrap();
+
+        let err = validate_list(&buf, check_not_zero).unwrap_err();
+
+        assert_eq!(err.to_string(), "invalid item 2: zero value");
+    }
+
+    #[test]
+    fn test_respond_with_schema() {
+        let val = (42u64, "hello".to_string());
+
+        let buf = respond_with_schema(&val, "pair:v1").unwrap();
+
+        let envelope: SchemaEnvelope<(u64, String)> = rmp_deserialize(&buf).unwrap();
+        assert_eq!(envelope.schema, "pair:v1");
+        assert_eq!(envelope.data, val);
+    }
+
+    #[test]
+    fn test_respond_with_schema_named() {
+        let buf = respond_with_schema(&7u8, "byte").unwrap();
+
+        let value: Value = rmp_deserialize(&buf).unwrap();
+        let schema = value.get(&value!("schema")).unwrap();
+        assert_eq!(schema.as_str(), Some("byte"));
+    }
+
+    fn check_bps(bps: u64) -> WasmResult<u64> {
+        require_range!(bps, 0..=10000, "bps out of range");
+        Ok(bps)
+    }
+
+    fn check_units(units: u64) -> WasmResult<u64> {
+        require_range!(units, 1..100, "units out of range");
+        Ok(units)
+    }
+
+    #[test]
+    fn test_require_range_inclusive() {
+        let lower = check_bps(0).unwrap();
+        let upper = check_bps(10000).unwrap();
+        let above = check_bps(10001).unwrap_err();
+
+        assert_eq!(lower, 0);
+        assert_eq!(upper, 10000);
+        assert_eq!(above.to_string(), "bps out of range");
+    }
+
+    #[test]
+    fn test_require_range_exclusive() {
+        let inside = check_units(99).unwrap();
+        let below = check_units(0).unwrap_err();
+        let above = check_units(100).unwrap_err();
+
+        assert_eq!(inside, 99);
+        assert_eq!(below.to_string(), "units out of range");
+        assert_eq!(above.to_string(), "units out of range");
+    }
 }
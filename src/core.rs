@@ -17,7 +17,10 @@
 
 //! Collection of structures that keep the SDK independent from the core
 
-use crate::ecdsa;
+use crate::{
+    common::{account_id_from_data, rmp_serialize, WasmResult},
+    ecdsa, ed25519,
+};
 use serde::{Deserialize, Serialize};
 
 /// Structure passed from the host to the wasm smart contracts.
@@ -90,9 +93,105 @@ macro_rules! named_unit_variant {
 }
 
 /// PublicKey type
+///
+/// New key types may be added, thus the enum is non exhaustive.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(tag = "type")]
+#[non_exhaustive]
 pub enum PublicKey {
     #[serde(rename = "ecdsa")]
     Ecdsa(ecdsa::PublicKey),
+    #[serde(rename = "ed25519")]
+    Ed25519(ed25519::PublicKey),
+}
+
+impl PublicKey {
+    /// ECDSA public key over the given curve.
+    pub fn ecdsa(curve: ecdsa::CurveId, value: Vec<u8>) -> Self {
+        PublicKey::Ecdsa(ecdsa::PublicKey {
+            curve_id: curve,
+            value,
+        })
+    }
+
+    /// Account identifier associated to the public key.
+    ///
+    /// The id is the base58 encoding of the SHA-256 multihash of the
+    /// MessagePack serialized key.
+    pub fn account_id(&self) -> WasmResult<String> {
+        let buf = rmp_serialize(self)?;
+        Ok(account_id_from_data(&buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecdsa::CurveId;
+
+    #[test]
+    fn public_key_ecdsa_ctor() {
+        let manual = PublicKey::Ecdsa(ecdsa::PublicKey {
+            curve_id: CurveId::Secp384R1,
+            value: vec![4, 1, 2, 3],
+        });
+
+        let key = PublicKey::ecdsa(CurveId::Secp384R1, vec![4, 1, 2, 3]);
+
+        assert_eq!(key, manual);
+        assert_eq!(
+            rmp_serialize(&key).unwrap(),
+            rmp_serialize(&manual).unwrap()
+        );
+    }
+
+    // Secp384r1 test key, the account id is derived outside of the SDK from
+    // the core encoding of the key: `[type, curve, value]` MessagePack array,
+    // SHA-256 multihash, base58 with the bitcoin alphabet.
+    const P384_PK_HEX: &str = "045936d631b849bb5760bcf62e0d1261b6b6e227dc0a3892cbeec91be069aaa25996f276b271c2c53cba4be96d67edcadd66b793456290609102d5401f413cd1b5f4130b9cfaa68d30d0d25c3704cb72734cd32064365ff7042f5a3eee09b06cc1";
+    const P384_ACCOUNT_ID: &str = "QmXLuXMqb8sNsxLPn29g272miF77y3GAiF23pz8DoHzX9p";
+
+    #[test]
+    fn public_key_account_id_vector() {
+        use sha2::{Digest, Sha256};
+
+        let value = hex::decode(P384_PK_HEX).unwrap();
+        let key = PublicKey::ecdsa(CurveId::Secp384R1, value.clone());
+        let mut encoded = b"\x93\xa5ecdsa\xa9secp384r1\xc4".to_vec();
+        encoded.push(value.len() as u8);
+        encoded.extend_from_slice(&value);
+        let mut multihash = vec![0x12, 0x20];
+        multihash.extend_from_slice(&Sha256::digest(&encoded));
+
+        let account_id = key.account_id().unwrap();
+
+        assert_eq!(rmp_serialize(&key).unwrap(), encoded);
+        assert_eq!(account_id, bs58::encode(multihash).into_string());
+        assert_eq!(account_id, P384_ACCOUNT_ID);
+    }
+
+    #[test]
+    fn public_key_ed25519_tagged() {
+        let key = PublicKey::Ed25519(ed25519::PublicKey { value: vec![7; 32] });
+
+        let buf = crate::rmp_serialize_named(&key).unwrap();
+
+        // { "type": "ed25519", "value": bin(0707...07) }
+        let expected = format!(
+            "82a474797065a765643235353139a576616c7565c420{}",
+            "07".repeat(32)
+        );
+        assert_eq!(hex::encode(buf), expected);
+    }
+
+    #[test]
+    fn public_key_ed25519_roundtrip() {
+        let key = PublicKey::Ed25519(ed25519::PublicKey { value: vec![7; 32] });
+
+        let named = crate::rmp_serialize_named(&key).unwrap();
+        let packed = rmp_serialize(&key).unwrap();
+
+        assert_eq!(crate::rmp_deserialize::<PublicKey>(&named).unwrap(), key);
+        assert_eq!(crate::rmp_deserialize::<PublicKey>(&packed).unwrap(), key);
+    }
 }
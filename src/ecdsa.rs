@@ -17,8 +17,10 @@
 
 //! Ecdsa utilities for the SDK
 
+use crate::common::{WasmError, WasmResult};
 use serde::{Deserialize, Serialize};
 
+crate::named_unit_variant!(secp256r1);
 crate::named_unit_variant!(secp384r1);
 
 /// ECDSA Curve
@@ -27,10 +29,31 @@ crate::named_unit_variant!(secp384r1);
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum CurveId {
+    #[serde(with = "secp256r1")]
+    Secp256R1,
     #[serde(with = "secp384r1")]
     Secp384R1,
 }
 
+impl CurveId {
+    /// Curve name, matching the serialized one.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CurveId::Secp256R1 => "secp256r1",
+            CurveId::Secp384R1 => "secp384r1",
+        }
+    }
+
+    /// Curve identified by the serialized name.
+    pub fn from_name(name: &str) -> WasmResult<CurveId> {
+        match name {
+            "secp256r1" => Ok(CurveId::Secp256R1),
+            "secp384r1" => Ok(CurveId::Secp384R1),
+            _ => Err(WasmError::new("unknown curve")),
+        }
+    }
+}
+
 /// ECDSA PublicKey
 ///
 /// **WARNING:** ANY MODIFICATION CAN BREAK COMPATIBILITY WITH THE CORE.
@@ -40,3 +63,66 @@ pub struct PublicKey {
     #[serde(with = "serde_bytes")]
     pub value: Vec<u8>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rmp_deserialize, rmp_serialize};
+
+    #[test]
+    fn curve_name_roundtrip() {
+        for curve in [CurveId::Secp256R1, CurveId::Secp384R1] {
+            let name = curve.name();
+
+            assert_eq!(CurveId::from_name(name).unwrap(), curve);
+            assert_eq!(
+                rmp_serialize(&curve).unwrap(),
+                rmp_serialize(&name).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn curve_unknown_name() {
+        let err = CurveId::from_name("secp256k1").unwrap_err();
+
+        assert_eq!(err.to_string(), "unknown curve");
+    }
+
+    #[test]
+    fn public_key_curves_roundtrip() {
+        for curve in [CurveId::Secp256R1, CurveId::Secp384R1] {
+            let pk = PublicKey {
+                curve_id: curve,
+                value: vec![4, 1, 2, 3],
+            };
+
+            let buf = rmp_serialize(&pk).unwrap();
+
+            assert_eq!(rmp_deserialize::<PublicKey>(&buf).unwrap(), pk);
+        }
+    }
+
+    #[test]
+    fn public_key_p256_serialization() {
+        let pk = PublicKey {
+            curve_id: CurveId::Secp256R1,
+            value: vec![4, 1, 2, 3],
+        };
+
+        let buf = rmp_serialize(&pk).unwrap();
+
+        // [ "secp256r1", bin(04010203) ]
+        assert_eq!(hex::encode(buf), "92a9736563703235367231c40404010203");
+    }
+
+    #[test]
+    fn public_key_unknown_curve() {
+        // [ "secp521r1", bin(04010203) ]
+        let buf = hex::decode("92a9736563703532317231c40404010203").unwrap();
+
+        let res = rmp_deserialize::<PublicKey>(&buf);
+
+        assert!(res.is_err());
+    }
+}
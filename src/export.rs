@@ -67,7 +67,12 @@ impl From<AppOutput<'_>> for WasmSlice {
 /// When C structure is returned by value, then its return address is expected to
 /// be passed as a first function parameter!
 #[no_mangle]
-extern "C" fn run(ctx_addr: i32, ctx_size: i32, args_addr: i32, args_size: i32) -> WasmSlice {
+pub(crate) extern "C" fn run(
+    ctx_addr: i32,
+    ctx_size: i32,
+    args_addr: i32,
+    args_size: i32,
+) -> WasmSlice {
     let slice = slice_from_mem(ctx_addr, ctx_size);
     let ctx: AppInput = match rmp_deserialize(slice) {
         Ok(value) => value,
@@ -229,4 +234,38 @@ pub mod tests {
 
         assert_eq!(hex::encode(buf), "92c2c4086261642061726773");
     }
+
+    #[test]
+    fn execute_method_with_success() {
+        let input = AppInput {
+            caller: CALLER,
+            owner: CALLER,
+            method: "foo",
+            depth: 0,
+            network: "skynet",
+            origin: CALLER,
+        };
+        let args = rmp_serde::to_vec_named(&value!({ "age": 33 })).unwrap();
+
+        let buf = crate::not_wasm::execute(&input, &args).unwrap();
+
+        let value: Value = rmp_deserialize(&buf).unwrap();
+        assert_eq!(value, 34);
+    }
+
+    #[test]
+    fn execute_method_with_failure() {
+        let input = AppInput {
+            caller: CALLER,
+            owner: CALLER,
+            method: "bar",
+            depth: 0,
+            network: "skynet",
+            origin: CALLER,
+        };
+
+        let err = crate::not_wasm::execute(&input, &[]).unwrap_err();
+
+        assert_eq!(err.to_string(), "bad args");
+    }
 }
@@ -17,7 +17,7 @@
 
 //! Opaque cryptographic secure hash used by the overall project.
 //!
-//! Current implementation uses SHA-256.
+//! Current implementation uses SHA-256, SHA-384 and SHA-512.
 //!
 //! The serialization uses [Multihash](https://multiformats.io/multihash) format
 //! to keep a door opened for future extensions.
@@ -25,13 +25,18 @@
 //! Complete multihash table lives
 //! [here](https://github.com/multiformats/multicodec/blob/master/table.csv).
 
-use sha2::{Digest, Sha256};
+use crate::common::{rmp_serialize, WasmError, WasmResult};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::{fmt, str::FromStr};
 
 /// Available hash algorithms.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
 pub enum HashAlgorithm {
     Identity,
     Sha256,
+    Sha384,
+    Sha512,
 }
 
 impl Default for HashAlgorithm {
@@ -44,9 +49,37 @@ impl Default for HashAlgorithm {
 const MULTIHASH_TYPE_IDENTITY: u8 = 0x00;
 /// Multihash SHA-256 type
 const MULTIHASH_TYPE_SHA256: u8 = 0x12;
+/// Multihash SHA-384 type
+const MULTIHASH_TYPE_SHA384: u8 = 0x20;
+/// Multihash SHA-512 type
+const MULTIHASH_TYPE_SHA512: u8 = 0x13;
+
+impl HashAlgorithm {
+    /// Multihash code of the algorithm.
+    pub fn multihash_code(&self) -> u64 {
+        let code = match self {
+            HashAlgorithm::Identity => MULTIHASH_TYPE_IDENTITY,
+            HashAlgorithm::Sha256 => MULTIHASH_TYPE_SHA256,
+            HashAlgorithm::Sha384 => MULTIHASH_TYPE_SHA384,
+            HashAlgorithm::Sha512 => MULTIHASH_TYPE_SHA512,
+        };
+        code as u64
+    }
+
+    /// Algorithm identified by the multihash code, if supported.
+    pub fn from_multihash_code(code: u64) -> Option<HashAlgorithm> {
+        match code {
+            code if code == MULTIHASH_TYPE_IDENTITY as u64 => Some(HashAlgorithm::Identity),
+            code if code == MULTIHASH_TYPE_SHA256 as u64 => Some(HashAlgorithm::Sha256),
+            code if code == MULTIHASH_TYPE_SHA384 as u64 => Some(HashAlgorithm::Sha384),
+            code if code == MULTIHASH_TYPE_SHA512 as u64 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+}
 
 /// Max length of multihash value.
-const MULTIHASH_VALUE_LEN_MAX: usize = 32;
+const MULTIHASH_VALUE_LEN_MAX: usize = 64;
 
 /// Max serialized length.
 const MULTIHASH_BYTES_LEN_MAX: usize = 2 + MULTIHASH_VALUE_LEN_MAX;
@@ -67,17 +100,15 @@ impl Hash {
         let mut hash = Hash::default();
         let hash_len = bytes.len();
 
-        let hash_alg = match alg {
-            HashAlgorithm::Identity => MULTIHASH_TYPE_IDENTITY,
-            HashAlgorithm::Sha256 => MULTIHASH_TYPE_SHA256,
-        };
-        hash.0[0] = hash_alg;
+        hash.0[0] = alg.multihash_code() as u8;
         hash.0[1] = hash_len as u8;
         hash.0[2..(2 + hash_len)].copy_from_slice(bytes);
         hash
     }
 
     /// Compute hash from arbitrary data.
+    ///
+    /// Panics if the `Identity` data is longer than 64 bytes.
     pub fn from_data(alg: HashAlgorithm, data: &[u8]) -> Self {
         match alg {
             HashAlgorithm::Sha256 => {
@@ -86,9 +117,122 @@ impl Hash {
                 let digest = hasher.finalize();
                 Hash::new(alg, digest.as_ref())
             }
+            HashAlgorithm::Sha384 => Hash::new(alg, Sha384::digest(data).as_ref()),
+            HashAlgorithm::Sha512 => Hash::new(alg, Sha512::digest(data).as_ref()),
             HashAlgorithm::Identity => Hash::new(alg, data),
         }
     }
+
+    /// Hash algorithm, `Identity` for unsupported multihash types.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::from_multihash_code(self.0[0] as u64).unwrap_or_default()
+    }
+
+    /// Hash value, without the multihash type and length prefix.
+    pub fn digest(&self) -> &[u8] {
+        &self.as_bytes()[2..]
+    }
+
+    /// Multihash bytes, i.e. the type and length prefix followed by the value.
+    ///
+    /// The trailing padding of the fixed size buffer is excluded.
+    pub fn as_bytes(&self) -> &[u8] {
+        let len = (self.0[1] as usize).min(MULTIHASH_VALUE_LEN_MAX);
+        &self.0[..2 + len]
+    }
+
+    // Parses the multihash bytes, validating the type byte and the length.
+    pub(crate) fn from_multihash(buf: &[u8]) -> Result<Self, HashParseError> {
+        let (code, len, bytes) = match buf {
+            [code, len, bytes @ ..] => (*code, *len as usize, bytes),
+            _ => return Err(HashParseError::LengthMismatch),
+        };
+        let alg =
+            HashAlgorithm::from_multihash_code(code as u64).ok_or(HashParseError::UnknownType)?;
+        match len == bytes.len() && len <= MULTIHASH_VALUE_LEN_MAX {
+            true => Ok(Hash::new(alg, bytes)),
+            false => Err(HashParseError::LengthMismatch),
+        }
+    }
+}
+
+/// Multihash parsing error.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum HashParseError {
+    /// Hex string with an odd number of digits.
+    OddLength,
+    /// Non hexadecimal digit.
+    InvalidDigit,
+    /// Unsupported multihash type byte.
+    UnknownType,
+    /// Value length not matching the multihash length byte.
+    LengthMismatch,
+}
+
+impl fmt::Display for HashParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            HashParseError::OddLength => "odd hex string length",
+            HashParseError::InvalidDigit => "invalid hex digit",
+            HashParseError::UnknownType => "unknown multihash type",
+            HashParseError::LengthMismatch => "multihash length mismatch",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for HashParseError {}
+
+/// Lowercase hex of the multihash bytes.
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_bytes()
+            .iter()
+            .try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+fn hex_digit(digit: u8) -> Result<u8, HashParseError> {
+    (digit as char)
+        .to_digit(16)
+        .map(|value| value as u8)
+        .ok_or(HashParseError::InvalidDigit)
+}
+
+/// Parse the hex of the multihash bytes.
+impl FromStr for Hash {
+    type Err = HashParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() % 2 != 0 {
+            return Err(HashParseError::OddLength);
+        }
+        let buf = s
+            .as_bytes()
+            .chunks(2)
+            .map(|digits| Ok((hex_digit(digits[0])? << 4) | hex_digit(digits[1])?))
+            .collect::<Result<Vec<u8>, HashParseError>>()?;
+        Hash::from_multihash(&buf)
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let buf: serde_bytes::ByteBuf = Deserialize::deserialize(deserializer)?;
+        Hash::from_multihash(&buf).map_err(D::Error::custom)
+    }
 }
 
 /// A trait for types that can be hashed.
@@ -96,3 +240,352 @@ pub trait Hashable {
     /// Hash using the chosen hash algorithm.
     fn hash(&self, alg: HashAlgorithm) -> Hash;
 }
+
+/// Panics if the `Identity` data is longer than 64 bytes, see `Hash::from_data`.
+impl Hashable for [u8] {
+    fn hash(&self, alg: HashAlgorithm) -> Hash {
+        Hash::from_data(alg, self)
+    }
+}
+
+/// Panics if the `Identity` data is longer than 64 bytes, see `Hash::from_data`.
+impl Hashable for Vec<u8> {
+    fn hash(&self, alg: HashAlgorithm) -> Hash {
+        Hash::from_data(alg, self)
+    }
+}
+
+/// Hash of the MessagePack serialized value.
+///
+/// Fails if the `Identity` algorithm is used and the serialized value is
+/// longer than 64 bytes.
+pub fn hash_serializable<T: Serialize>(val: &T, alg: HashAlgorithm) -> WasmResult<Hash> {
+    let buf = rmp_serialize(val)?;
+    if alg == HashAlgorithm::Identity && buf.len() > MULTIHASH_VALUE_LEN_MAX {
+        return Err(WasmError::new("identity hash value too long"));
+    }
+    Ok(buf.hash(alg))
+}
+
+/// HMAC block size for SHA-256.
+const HMAC_SHA256_BLOCK_LEN: usize = 64;
+
+/// Compute a keyed tag of the message using HMAC-SHA256 (RFC 2104).
+///
+/// The secret shall never be exposed (e.g. returned by a method or emitted),
+/// anyone knowing it can forge valid tags.
+pub fn keyed_tag(secret: &[u8], message: &[u8]) -> Hash {
+    let mut key = [0u8; HMAC_SHA256_BLOCK_LEN];
+    if secret.len() > HMAC_SHA256_BLOCK_LEN {
+        key[..32].copy_from_slice(Sha256::digest(secret).as_ref());
+    } else {
+        key[..secret.len()].copy_from_slice(secret);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+    inner.update(message);
+    let inner = inner.finalize();
+
+    let mut outer = Sha256::new();
+    outer.update(key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+    outer.update(inner);
+    Hash::new(HashAlgorithm::Sha256, outer.finalize().as_ref())
+}
+
+/// Verify a tag computed via `keyed_tag`.
+///
+/// The comparison is performed in constant time.
+pub fn verify_tag(secret: &[u8], message: &[u8], tag: &Hash) -> bool {
+    let expected = keyed_tag(secret, message);
+    expected
+        .0
+        .iter()
+        .zip(tag.0.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Domain-separated digest of the message.
+///
+/// The digest is the SHA-256 of `len(domain) || domain || message`, where
+/// `len(domain)` is the domain bytes length as a 4 bytes big-endian integer.
+/// Off-chain signers shall sign the digest value (i.e. the 32 bytes without
+/// the multihash prefix), see `host_wrap::verify_domain_signed`.
+pub fn domain_digest(domain: &str, message: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update((domain.len() as u32).to_be_bytes());
+    hasher.update(domain.as_bytes());
+    hasher.update(message);
+    Hash::new(HashAlgorithm::Sha256, hasher.finalize().as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4231 test case 2.
+    const HMAC_KEY: &[u8] = b"Jefe";
+    const HMAC_DATA: &[u8] = b"what do ya want for nothing?";
+    const HMAC_HEX: &str = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+
+    #[test]
+    fn keyed_tag_hmac_sha256() {
+        let tag = keyed_tag(HMAC_KEY, HMAC_DATA);
+
+        assert_eq!(
+            tag,
+            Hash::new(HashAlgorithm::Sha256, &hex::decode(HMAC_HEX).unwrap())
+        );
+    }
+
+    #[test]
+    fn keyed_tag_long_secret() {
+        let secret = [b'k'; 100];
+
+        let tag = keyed_tag(&secret, b"msg");
+
+        let expected = "bd56a1782c2830e8abc6ed866a57a1230661e650b84c62f7ee3accc5fa5af491";
+        assert_eq!(
+            tag,
+            Hash::new(HashAlgorithm::Sha256, &hex::decode(expected).unwrap())
+        );
+    }
+
+    #[test]
+    fn verify_tag_valid() {
+        let tag = keyed_tag(HMAC_KEY, HMAC_DATA);
+
+        assert!(verify_tag(HMAC_KEY, HMAC_DATA, &tag));
+    }
+
+    #[test]
+    fn verify_tag_tampered_message() {
+        let tag = keyed_tag(HMAC_KEY, HMAC_DATA);
+
+        assert!(!verify_tag(
+            HMAC_KEY,
+            b"what do ya want for everything?",
+            &tag
+        ));
+    }
+
+    #[test]
+    fn multihash_code_roundtrip() {
+        for alg in [
+            HashAlgorithm::Identity,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ] {
+            let code = alg.multihash_code();
+
+            assert_eq!(HashAlgorithm::from_multihash_code(code), Some(alg));
+        }
+        assert_eq!(HashAlgorithm::Sha256.multihash_code(), 0x12);
+    }
+
+    #[test]
+    fn multihash_code_unknown() {
+        assert_eq!(HashAlgorithm::from_multihash_code(0x14), None);
+    }
+
+    #[test]
+    fn sha384_multihash() {
+        let hash = Hash::from_data(HashAlgorithm::Sha384, b"abc");
+
+        let expected = "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed\
+                        8086072ba1e7cc2358baeca134c825a7";
+        assert_eq!(hash.0[..2], [0x20, 48]);
+        assert_eq!(hex::encode(&hash.0[2..50]), expected);
+        assert!(hash.0[50..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn sha512_multihash() {
+        let hash = Hash::from_data(HashAlgorithm::Sha512, b"abc");
+
+        let expected = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                        2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+        assert_eq!(hash.0[..2], [0x13, 64]);
+        assert_eq!(hex::encode(&hash.0[2..]), expected);
+    }
+
+    #[test]
+    fn domain_digest_framing() {
+        let digest = domain_digest("ab", b"c");
+
+        let mut buf = vec![0, 0, 0, 2];
+        buf.extend_from_slice(b"abc");
+        assert_eq!(digest, Hash::from_data(HashAlgorithm::Sha256, &buf));
+        assert_eq!(digest, domain_digest("ab", b"c"));
+    }
+
+    #[test]
+    fn domain_digest_separation() {
+        let digest = domain_digest("transfer", b"msg");
+
+        assert_ne!(digest, domain_digest("withdraw", b"msg"));
+        assert_ne!(domain_digest("ab", b"c"), domain_digest("a", b"bc"));
+    }
+
+    const ABC_SHA256_HEX: &str =
+        "1220ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn hash_serialize() {
+        let hash = Hash::from_data(HashAlgorithm::Sha256, b"abc");
+
+        let buf = crate::rmp_serialize(&hash).unwrap();
+
+        assert_eq!(hex::encode(buf), format!("c422{}", ABC_SHA256_HEX));
+    }
+
+    #[test]
+    fn hash_deserialize() {
+        let buf = hex::decode(format!("c422{}", ABC_SHA256_HEX)).unwrap();
+
+        let hash: Hash = crate::rmp_deserialize(&buf).unwrap();
+
+        assert_eq!(hash, Hash::from_data(HashAlgorithm::Sha256, b"abc"));
+    }
+
+    #[test]
+    fn hash_deserialize_truncated() {
+        let mut multihash = hex::decode(ABC_SHA256_HEX).unwrap();
+        multihash.pop();
+        let buf = crate::rmp_serialize(&serde_bytes::ByteBuf::from(multihash)).unwrap();
+
+        let res = crate::rmp_deserialize::<Hash>(&buf);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn hash_deserialize_unknown_type() {
+        let mut multihash = hex::decode(ABC_SHA256_HEX).unwrap();
+        multihash[0] = 0x14;
+        let buf = crate::rmp_serialize(&serde_bytes::ByteBuf::from(multihash)).unwrap();
+
+        let res = crate::rmp_deserialize::<Hash>(&buf);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn hash_display() {
+        let hash = Hash::from_data(HashAlgorithm::Sha256, b"abc");
+
+        assert_eq!(hash.to_string(), ABC_SHA256_HEX);
+    }
+
+    #[test]
+    fn hash_from_str_roundtrip() {
+        for alg in [HashAlgorithm::Sha256, HashAlgorithm::Sha512] {
+            let hash = Hash::from_data(alg, b"abc");
+
+            assert_eq!(hash.to_string().parse::<Hash>().unwrap(), hash);
+        }
+        assert_eq!("0000".parse::<Hash>().unwrap(), Hash::default());
+    }
+
+    #[test]
+    fn hash_from_str_errors() {
+        assert_eq!("122".parse::<Hash>(), Err(HashParseError::OddLength));
+        assert_eq!("12zz".parse::<Hash>(), Err(HashParseError::InvalidDigit));
+        assert_eq!("1401ff".parse::<Hash>(), Err(HashParseError::UnknownType));
+        assert_eq!(
+            "1202ff".parse::<Hash>(),
+            Err(HashParseError::LengthMismatch)
+        );
+    }
+
+    #[test]
+    fn hash_accessors() {
+        let hash = Hash::from_data(HashAlgorithm::Sha256, b"abc");
+
+        assert_eq!(hash.algorithm(), HashAlgorithm::Sha256);
+        assert_eq!(hex::encode(hash.digest()), &ABC_SHA256_HEX[4..]);
+        assert_eq!(hex::encode(hash.as_bytes()), ABC_SHA256_HEX);
+    }
+
+    #[test]
+    fn hash_accessors_default() {
+        let hash = Hash::default();
+
+        assert_eq!(hash.algorithm(), HashAlgorithm::Identity);
+        assert!(hash.digest().is_empty());
+        assert_eq!(hash.as_bytes(), [0, 0]);
+    }
+
+    #[derive(Serialize)]
+    struct Order<'a> {
+        account: &'a str,
+        units: u64,
+    }
+
+    #[test]
+    fn hashable_bytes() {
+        let vec = b"abc".to_vec();
+
+        assert_eq!(vec.hash(HashAlgorithm::Sha256).to_string(), ABC_SHA256_HEX);
+        assert_eq!(
+            vec[..].hash(HashAlgorithm::Sha256).to_string(),
+            ABC_SHA256_HEX
+        );
+    }
+
+    #[test]
+    fn hash_serializable_stable() {
+        let order = Order {
+            account: "foo",
+            units: 42,
+        };
+
+        let hash1 = hash_serializable(&order, HashAlgorithm::Sha256).unwrap();
+        let hash2 = hash_serializable(&order, HashAlgorithm::Sha256).unwrap();
+
+        let buf = rmp_serialize(&order).unwrap();
+        assert_eq!(hash1, hash2);
+        assert_eq!(hash1, Hash::from_data(HashAlgorithm::Sha256, &buf));
+    }
+
+    #[test]
+    fn hash_serializable_different_values() {
+        let order1 = Order {
+            account: "foo",
+            units: 42,
+        };
+        let order2 = Order {
+            account: "foo",
+            units: 43,
+        };
+
+        let hash1 = hash_serializable(&order1, HashAlgorithm::Sha256).unwrap();
+        let hash2 = hash_serializable(&order2, HashAlgorithm::Sha256).unwrap();
+
+        assert_ne!(hash1, hash2);
+    }
+
+    #[test]
+    fn hash_serializable_identity_too_long() {
+        let account = "QmYHnEQLdf5h7KYbjFPuHSRk2SPgdXrJWFh5W696HPfq7i".repeat(2);
+        let order = Order {
+            account: &account,
+            units: 42,
+        };
+        let short = Order {
+            account: "foo",
+            units: 42,
+        };
+
+        let err = hash_serializable(&order, HashAlgorithm::Identity).unwrap_err();
+        let hash = hash_serializable(&short, HashAlgorithm::Identity).unwrap();
+        let sha256 = hash_serializable(&order, HashAlgorithm::Sha256);
+
+        assert!(rmp_serialize(&order).unwrap().len() > MULTIHASH_VALUE_LEN_MAX);
+        assert_eq!(err.to_string(), "identity hash value too long");
+        assert_eq!(hash.digest(), rmp_serialize(&short).unwrap());
+        assert!(sha256.is_ok());
+    }
+}
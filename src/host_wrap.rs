@@ -20,10 +20,13 @@
 use crate::{
     common::*,
     core::{AppOutput, PublicKey},
-    tai::{AssetLockArgs, AssetTransferArgs, LockType},
+    hash::{domain_digest, Hash, HashAlgorithm},
+    tai::{Asset, AssetLockArgs, AssetTransferArgs, LockType},
 };
 
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_value::{value, Value};
+use std::cell::RefCell;
 
 // Host functions imported
 extern "C" {
@@ -130,6 +133,43 @@ pub fn emit_data(event_name: &str, event_data: &[u8]) {
     }
 }
 
+/// Emit a typed event, the payload is MessagePack encoded.
+pub fn emit<T: Serialize>(event_name: &str, event: &T) -> WasmResult<()> {
+    let buf = rmp_serialize(event)?;
+    emit_data(event_name, &buf);
+    Ok(())
+}
+
+/// Emit a sequence of events, in the given order.
+pub fn emit_batch(events: &[(&str, &[u8])]) {
+    events
+        .iter()
+        .for_each(|(event_name, event_data)| emit_data(event_name, event_data));
+}
+
+/// Payload of the `progress` event.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ProgressEvent<'a> {
+    /// Name of the long-running operation.
+    pub operation: &'a str,
+    /// Number of processed items.
+    pub done: u64,
+    /// Total number of items.
+    pub total: u64,
+}
+
+/// Emit a `progress` event for long-running operations.
+pub fn emit_progress(operation: &str, done: u64, total: u64) {
+    let event = ProgressEvent {
+        operation,
+        done,
+        total,
+    };
+    if let Ok(buf) = rmp_serialize_named(&event) {
+        emit_data("progress", &buf);
+    }
+}
+
 /// Load account data associated to the given key.
 pub fn load_data(key: &str) -> Vec<u8> {
     let key_addr = slice_to_mem(key.as_bytes());
@@ -137,6 +177,25 @@ pub fn load_data(key: &str) -> Vec<u8> {
     slice_from_wslice(wslice).to_vec()
 }
 
+/// Load the typed account data associated to the given key.
+///
+/// Fails if the key is absent or the data is corrupt.
+pub fn load_data_typed<T: DeserializeOwned>(key: &str) -> WasmResult<T> {
+    load_data_typed_opt(key)?.ok_or_else(|| WasmError::new("data not found"))
+}
+
+/// Load the typed account data associated to the given key.
+///
+/// Returns `None` if the key is absent (the host returns no data), this allows
+/// to distinguish an unset value from a default one. Fails on corrupt data.
+pub fn load_data_typed_opt<T: DeserializeOwned>(key: &str) -> WasmResult<Option<T>> {
+    let buf = load_data(key);
+    match buf.is_empty() {
+        true => Ok(None),
+        false => rmp_deserialize(&buf).map(Some),
+    }
+}
+
 /// Get the account contract to the given account id
 pub fn get_account_contract(id: &str) -> Vec<u8> {
     let id_addr = slice_to_mem(id.as_bytes());
@@ -144,6 +203,23 @@ pub fn get_account_contract(id: &str) -> Vec<u8> {
     slice_from_wslice(wslice).to_vec()
 }
 
+/// Get the hash of the contract bound to the given account id.
+///
+/// Returns `None` if the account has no contract or if the stored bytes are
+/// not a multihash using a supported algorithm.
+pub fn get_account_contract_hash(id: &str) -> Option<Hash> {
+    let buf = get_account_contract(id);
+    Hash::from_multihash(&buf).ok()
+}
+
+/// Fails if the contract bound to the account is not the expected one.
+pub fn require_contract(account: &str, expected_hash: &Hash) -> WasmResult<()> {
+    match get_account_contract_hash(account) {
+        Some(hash) if hash == *expected_hash => Ok(()),
+        _ => Err(WasmError::new("unexpected contract")),
+    }
+}
+
 /// Check if the given account has a contract with a specific method
 pub fn is_callable(id: &str, method: &str) -> bool {
     let id_addr = slice_to_mem(id.as_bytes());
@@ -162,7 +238,7 @@ pub fn get_data_keys(pattern: &str) -> WasmResult<Vec<String>> {
     let res: AppOutput = rmp_deserialize(&buf)?;
     match res.success {
         true => rmp_deserialize::<Vec<String>>(res.data),
-        false => Err(WasmError::new(String::from_utf8_lossy(res.data).as_ref())),
+        false => Err(WasmError::from_app_output(&res)),
     }
 }
 
@@ -173,12 +249,601 @@ pub fn store_data(key: &str, buf: &[u8]) {
     unsafe { hf_store_data(key_addr, key.len() as i32, data_addr, buf.len() as i32) };
 }
 
+/// Store the typed account data associated to the given key.
+pub fn store_data_typed<T: Serialize>(key: &str, value: &T) -> WasmResult<()> {
+    let buf = rmp_serialize(value)?;
+    store_data(key, &buf);
+    Ok(())
+}
+
+/// Data key prefixes reserved for the state managed by the SDK.
+pub const RESERVED_PREFIXES: &[&str] = &["__sdk:"];
+
+/// Check if the data key falls within one of the `RESERVED_PREFIXES`.
+pub fn is_reserved_key(key: &str) -> bool {
+    RESERVED_PREFIXES
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
+}
+
+/// Store account data associated to the given key.
+///
+/// Differently from `store_data`, writes to reserved keys are rejected.
+pub fn store_data_safe(key: &str, buf: &[u8]) -> WasmResult<()> {
+    if is_reserved_key(key) {
+        return Err(WasmError::new("reserved data key"));
+    }
+    store_data(key, buf);
+    Ok(())
+}
+
+/// Compare the account data keys with the expected ones.
+///
+/// Returns the `(missing, extra)` keys, the extra ones are sorted.
+/// Keys reserved to the SDK are never reported as extra.
+pub fn data_key_diff(expected: &[&str]) -> WasmResult<(Vec<String>, Vec<String>)> {
+    let keys = get_data_keys("*")?;
+    let missing = expected
+        .iter()
+        .filter(|key| !keys.iter().any(|k| k == *key))
+        .map(|key| key.to_string())
+        .collect();
+    let mut extra: Vec<String> = keys
+        .into_iter()
+        .filter(|key| !is_reserved_key(key) && !expected.contains(&key.as_str()))
+        .collect();
+    extra.sort();
+    Ok((missing, extra))
+}
+
+/// Dump the account data as a `Value::Map` keyed by data key.
+///
+/// Each value is decoded as MessagePack, values that can't be decoded are
+/// reported as `Value::Bytes`. Meant for block explorers, see `state_method!`.
+pub fn state_dump() -> WasmResult<Value> {
+    fn decode(buf: Vec<u8>) -> Value {
+        let mut rest = buf.as_slice();
+        let value = Value::deserialize(&mut rmp_serde::Deserializer::new(&mut rest));
+        match value {
+            Ok(value) if rest.is_empty() => value,
+            _ => Value::Bytes(buf),
+        }
+    }
+
+    let state = get_data_keys("*")?
+        .into_iter()
+        .map(|key| {
+            let value = decode(load_data(&key));
+            (Value::String(key), value)
+        })
+        .collect();
+    Ok(Value::Map(state))
+}
+
+/// Set of strings stored in the account data.
+///
+/// Each member is stored under its own `<name>:<member>` key, thus adding or
+/// removing a member doesn't require to load the whole set.
+pub struct DataSet {
+    name: String,
+}
+
+impl DataSet {
+    /// Set marker value stored for each member.
+    const MEMBER_MARKER: &'static [u8] = &[0xc3];
+
+    /// Constructor.
+    pub fn new(name: &str) -> Self {
+        DataSet {
+            name: name.to_owned(),
+        }
+    }
+
+    fn member_key(&self, member: &str) -> String {
+        format!("{}:{}", self.name, member)
+    }
+
+    /// Add a member to the set.
+    pub fn insert(&self, member: &str) {
+        store_data(&self.member_key(member), Self::MEMBER_MARKER);
+    }
+
+    /// Remove a member from the set.
+    pub fn remove(&self, member: &str) {
+        remove_data(&self.member_key(member));
+    }
+
+    /// Check if a member is in the set.
+    pub fn contains(&self, member: &str) -> bool {
+        !load_data(&self.member_key(member)).is_empty()
+    }
+
+    /// Get all the members of the set.
+    pub fn members(&self) -> WasmResult<Vec<String>> {
+        let prefix = self.member_key("");
+        let keys = get_data_keys(&format!("{}*", prefix))?;
+        Ok(keys
+            .into_iter()
+            .map(|key| key[prefix.len()..].to_owned())
+            .collect())
+    }
+}
+
+/// Set of boolean flags packed in a byte vector stored under a single data key.
+///
+/// The flag `index` is the bit `index % 8` of the byte `index / 8`. Changes are
+/// persisted by `store`.
+pub struct BitSet {
+    key: String,
+    bytes: Vec<u8>,
+}
+
+impl BitSet {
+    /// Load the set stored under the given data key.
+    pub fn load(key: &str) -> Self {
+        BitSet {
+            key: key.to_owned(),
+            bytes: load_data(key),
+        }
+    }
+
+    /// Store the set under its data key.
+    pub fn store(&self) {
+        store_data(&self.key, &self.bytes);
+    }
+
+    /// Set the flag.
+    pub fn set(&mut self, index: usize) {
+        let byte = index / 8;
+        if byte >= self.bytes.len() {
+            self.bytes.resize(byte + 1, 0);
+        }
+        self.bytes[byte] |= 1 << (index % 8);
+    }
+
+    /// Clear the flag.
+    pub fn clear(&mut self, index: usize) {
+        if let Some(byte) = self.bytes.get_mut(index / 8) {
+            *byte &= !(1 << (index % 8));
+        }
+        while self.bytes.last() == Some(&0) {
+            self.bytes.pop();
+        }
+    }
+
+    /// Get the flag.
+    pub fn get(&self, index: usize) -> bool {
+        self.bytes
+            .get(index / 8)
+            .map(|byte| byte & (1 << (index % 8)) != 0)
+            .unwrap_or_default()
+    }
+}
+
+/// Policy restricting the callers allowed to execute a method.
+///
+/// The allowed callers are kept as a `DataSet` so the set can be changed over
+/// time (e.g. by a committee).
+pub struct CallerPolicy {
+    allowed: DataSet,
+}
+
+impl CallerPolicy {
+    /// Load the policy stored under the given data key.
+    pub fn new(key: &str) -> Self {
+        CallerPolicy {
+            allowed: DataSet::new(key),
+        }
+    }
+
+    /// Allow an account to call the guarded methods.
+    pub fn allow(&self, account: &str) {
+        self.allowed.insert(account);
+    }
+
+    /// Revoke an account from calling the guarded methods.
+    pub fn revoke(&self, account: &str) {
+        self.allowed.remove(account);
+    }
+
+    /// Check if an account is allowed by the policy.
+    pub fn is_allowed(&self, account: &str) -> bool {
+        self.allowed.contains(account)
+    }
+
+    /// Fails if the context caller is not allowed by the policy.
+    pub fn enforce(&self, ctx: &AppContext) -> WasmResult<()> {
+        match self.is_allowed(ctx.caller) {
+            true => Ok(()),
+            false => Err(WasmError::new("caller not allowed")),
+        }
+    }
+}
+
+/// Account roles (e.g. minter, pauser).
+///
+/// The members of each role are kept as a `DataSet` named `role:<role>`, thus
+/// each grant is stored under the `role:<role>:<account>` key.
+pub struct Roles;
+
+impl Roles {
+    fn members(role: &str) -> DataSet {
+        DataSet::new(&format!("role:{}", role))
+    }
+
+    /// Grant the role to an account.
+    pub fn grant(role: &str, account: &str) {
+        Self::members(role).insert(account);
+    }
+
+    /// Revoke the role from an account.
+    pub fn revoke(role: &str, account: &str) {
+        Self::members(role).remove(account);
+    }
+
+    /// Check if an account has the role.
+    pub fn has_role(role: &str, account: &str) -> bool {
+        Self::members(role).contains(account)
+    }
+
+    /// Fails if the context caller doesn't have the role.
+    pub fn require_role(ctx: &AppContext, role: &str) -> WasmResult<()> {
+        match Self::has_role(role, ctx.caller) {
+            true => Ok(()),
+            false => Err(WasmError::new(&format!("missing role: {}", role))),
+        }
+    }
+}
+
+/// Global switch to pause the state-changing methods of the contract.
+///
+/// Guarded methods shall start with `when_not_paused!()`.
+pub struct Pausable;
+
+impl Pausable {
+    const PAUSED_KEY: &'static str = "__sdk:paused";
+
+    /// Pause the contract.
+    pub fn pause() {
+        store_data(Self::PAUSED_KEY, &[0xc3]);
+    }
+
+    /// Unpause the contract.
+    pub fn unpause() {
+        remove_data(Self::PAUSED_KEY);
+    }
+
+    /// Check if the contract is paused.
+    pub fn is_paused() -> bool {
+        !load_data(Self::PAUSED_KEY).is_empty()
+    }
+
+    /// Fails if the contract is paused.
+    pub fn require_not_paused() -> WasmResult<()> {
+        match Self::is_paused() {
+            true => Err(WasmError::new("contract is paused")),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Time-bounded exclusive lease over a resource.
+///
+/// Leases coordinate methods, they are unrelated to the asset locks.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Lease {
+    /// Lease holder.
+    pub holder: String,
+    /// Lease expiration timestamp.
+    pub expires_at: u64,
+}
+
+impl Lease {
+    fn key(resource: &str) -> String {
+        format!("__sdk:lease:{}", resource)
+    }
+
+    /// Get the unexpired lease over the resource, if any.
+    pub fn get(resource: &str) -> WasmResult<Option<Lease>> {
+        let lease: Option<Lease> = load_data_typed_opt(&Self::key(resource))?;
+        Ok(lease.filter(|lease| lease.expires_at > get_block_time()))
+    }
+
+    /// Acquire the lease over the resource for `ttl_secs` seconds.
+    ///
+    /// Fails if the resource is leased by another holder, the current holder
+    /// can acquire it again to renew the lease.
+    pub fn acquire(resource: &str, holder: &str, ttl_secs: u64) -> WasmResult<()> {
+        if let Some(lease) = Self::get(resource)? {
+            if lease.holder != holder {
+                return Err(WasmError::new("resource already leased"));
+            }
+        }
+        let lease = Lease {
+            holder: holder.to_owned(),
+            expires_at: get_block_time().saturating_add(ttl_secs),
+        };
+        store_data_typed(&Self::key(resource), &lease)
+    }
+
+    /// Release the lease over the resource.
+    ///
+    /// Fails if the resource is leased by another holder.
+    pub fn release(resource: &str, holder: &str) -> WasmResult<()> {
+        if let Some(lease) = Self::get(resource)? {
+            if lease.holder != holder {
+                return Err(WasmError::new("resource already leased"));
+            }
+        }
+        remove_data(&Self::key(resource));
+        Ok(())
+    }
+}
+
+/// Per-account spending limit of an asset.
+///
+/// The spent amount is reset when the period elapses.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct SpendingLimit {
+    /// Max units that can be spent within a period.
+    pub limit: u64,
+    /// Period duration in seconds.
+    pub period: u64,
+    /// Units spent within the current period.
+    pub spent: u64,
+    /// Current period start timestamp.
+    pub period_start: u64,
+}
+
+impl SpendingLimit {
+    fn key(account: &str, asset: &str) -> String {
+        format!("__sdk:spending:{}:{}", asset, account)
+    }
+
+    /// Set the account spending limit for the asset, starting a new period.
+    pub fn set(account: &str, asset: &str, limit: u64, period: u64, now: u64) -> WasmResult<()> {
+        let value = SpendingLimit {
+            limit,
+            period,
+            spent: 0,
+            period_start: now,
+        };
+        let buf = rmp_serialize(&value)?;
+        store_data(&Self::key(account, asset), &buf);
+        Ok(())
+    }
+
+    /// Load the account spending limit for the asset, if any.
+    pub fn load(account: &str, asset: &str) -> WasmResult<Option<SpendingLimit>> {
+        let buf = load_data(&Self::key(account, asset));
+        match buf.is_empty() {
+            true => Ok(None),
+            false => rmp_deserialize(&buf).map(Some),
+        }
+    }
+
+    /// Record that the account is spending `units` of the asset.
+    ///
+    /// Fails if the units exceeds the limit of the current period.
+    /// Accounts without a spending limit are not restricted.
+    pub fn check_and_record(account: &str, asset: &str, units: u64, now: u64) -> WasmResult<()> {
+        let mut value = match Self::load(account, asset)? {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        if now >= value.period_start.saturating_add(value.period) {
+            value.spent = 0;
+            value.period_start = now;
+        }
+        value.spent = match value.spent.checked_add(units) {
+            Some(spent) if spent <= value.limit => spent,
+            _ => return Err(WasmError::new("spending limit exceeded")),
+        };
+        let buf = rmp_serialize(&value)?;
+        store_data(&Self::key(account, asset), &buf);
+        Ok(())
+    }
+}
+
+fn allowance_key(owner: &str, spender: &str) -> String {
+    format!("__sdk:allowance:{}:{}", owner, spender)
+}
+
+/// Set the units the `spender` is allowed to spend on behalf of the `owner`.
+pub fn approve_allowance(owner: &str, spender: &str, units: u64) -> WasmResult<()> {
+    let buf = match units {
+        0 => vec![],
+        _ => rmp_serialize(&units)?,
+    };
+    store_data(&allowance_key(owner, spender), &buf);
+    Ok(())
+}
+
+/// Get the units the `spender` is still allowed to spend on behalf of the `owner`.
+pub fn allowance(owner: &str, spender: &str) -> WasmResult<u64> {
+    let buf = load_data(&allowance_key(owner, spender));
+    match buf.is_empty() {
+        true => Ok(0),
+        false => rmp_deserialize(&buf),
+    }
+}
+
+/// Consume `units` from the allowance, returning the remaining units.
+pub fn spend_allowance(owner: &str, spender: &str, units: u64) -> WasmResult<u64> {
+    let remaining = allowance(owner, spender)?
+        .checked_sub(units)
+        .ok_or_else(|| WasmError::new("allowance exceeded"))?;
+    approve_allowance(owner, spender, remaining)?;
+    Ok(remaining)
+}
+
+/// Allowance as a `{owner, spender, remaining}` map, suitable as a method return.
+pub fn allowance_response(owner: &str, spender: &str) -> WasmResult<Value> {
+    let remaining = allowance(owner, spender)?;
+    Ok(value!({
+        "owner": owner,
+        "spender": spender,
+        "remaining": remaining,
+    }))
+}
+
+/// Accept the next message of an ordered stream.
+///
+/// Requires `seq` to be the successor of the last accepted one (the first
+/// accepted sequence number is `1`) and records it, rejecting replays and gaps.
+pub fn require_next_sequence(stream: &str, seq: u64) -> WasmResult<()> {
+    let key = format!("__sdk:sequence:{}", stream);
+    let buf = load_data(&key);
+    let last: u64 = match buf.is_empty() {
+        true => 0,
+        false => rmp_deserialize(&buf)?,
+    };
+    if last.checked_add(1) != Some(seq) {
+        return Err(WasmError::new("unexpected sequence number"));
+    }
+    store_data(&key, &rmp_serialize(&seq)?);
+    Ok(())
+}
+
+/// Allow the tagged action to run at most once per block.
+///
+/// The host doesn't expose the block height, thus blocks are identified by
+/// their timestamp (see `get_block_time`). The current block is recorded,
+/// a second run within the same block is rejected.
+pub fn require_once_per_block(method_tag: &str) -> WasmResult<()> {
+    let key = format!("__sdk:last_run:{}", method_tag);
+    let block = get_block_time();
+    if let Some(last) = load_data_typed_opt::<u64>(&key)? {
+        if last == block {
+            return Err(WasmError::new("already called this block"));
+        }
+    }
+    store_data(&key, &rmp_serialize(&block)?);
+    Ok(())
+}
+
+/// Payload of the `credit` and `debit` events.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BalanceEvent<'a> {
+    pub account: &'a str,
+    pub asset: &'a str,
+    pub units: u64,
+    pub balance: u64,
+}
+
+fn balance_key(account: &str, asset: &str) -> String {
+    format!("__sdk:balance:{}:{}", asset, account)
+}
+
+/// Get the internal balance of the account for the asset.
+pub fn internal_balance(account: &str, asset: &str) -> WasmResult<u64> {
+    let buf = load_data(&balance_key(account, asset));
+    match buf.is_empty() {
+        true => Ok(0),
+        false => rmp_deserialize(&buf),
+    }
+}
+
+fn update_balance(
+    event: &str,
+    account: &str,
+    asset: &str,
+    units: u64,
+    balance: u64,
+) -> WasmResult<u64> {
+    store_data(&balance_key(account, asset), &rmp_serialize(&balance)?);
+    let event_data = rmp_serialize_named(&BalanceEvent {
+        account,
+        asset,
+        units,
+        balance,
+    })?;
+    emit_data(event, &event_data);
+    match event {
+        "credit" => record_group_audit(event, "", account, asset, units)?,
+        _ => record_group_audit(event, account, "", asset, units)?,
+    }
+    Ok(balance)
+}
+
+/// Add units to the internal balance of the account, returning the new balance.
+///
+/// Emits a `credit` event.
+pub fn credit(account: &str, asset: &str, units: u64) -> WasmResult<u64> {
+    let balance = internal_balance(account, asset)?
+        .checked_add(units)
+        .ok_or_else(|| WasmError::new("balance overflow"))?;
+    update_balance("credit", account, asset, units, balance)
+}
+
+/// Remove units from the internal balance of the account, returning the new balance.
+///
+/// Emits a `debit` event.
+pub fn debit(account: &str, asset: &str, units: u64) -> WasmResult<u64> {
+    let balance = internal_balance(account, asset)?
+        .checked_sub(units)
+        .ok_or_else(|| WasmError::new("insufficient balance"))?;
+    update_balance("debit", account, asset, units, balance)
+}
+
+/// Width of the zero-padded index used by numerically-keyed records.
+/// Enough to represent any `u64` while keeping the keys sortable.
+const NUMERIC_KEY_WIDTH: usize = 20;
+
+fn numeric_key(prefix: &str, index: u64) -> String {
+    format!("{}{:0width$}", prefix, index, width = NUMERIC_KEY_WIDTH)
+}
+
+/// Store a typed value under the `prefix` followed by the zero-padded `index`.
+pub fn store_numeric<T: Serialize>(prefix: &str, index: u64, value: &T) -> WasmResult<()> {
+    let buf = rmp_serialize(value)?;
+    store_data(&numeric_key(prefix, index), &buf);
+    Ok(())
+}
+
+/// Load the records stored via `store_numeric` with index in the
+/// inclusive range `from..=to`.
+///
+/// Missing indices are skipped. Each index within the range is loaded,
+/// thus keep the range reasonably small.
+pub fn iter_numeric_range<T: DeserializeOwned>(
+    prefix: &str,
+    from: u64,
+    to: u64,
+) -> WasmResult<Vec<(u64, T)>> {
+    let mut records = Vec::new();
+    for index in from..=to {
+        let buf = load_data(&numeric_key(prefix, index));
+        if !buf.is_empty() {
+            records.push((index, rmp_deserialize(&buf)?));
+        }
+    }
+    Ok(records)
+}
+
 /// Remove account data associated to the given key.
 pub fn remove_data(key: &str) {
     let key_addr = slice_to_mem(key.as_bytes());
     unsafe { hf_remove_data(key_addr, key.len() as i32) };
 }
 
+/// Move the account data associated to `old_key` under `new_key`.
+///
+/// Returns whether some data has been moved. Fails, without moving anything,
+/// if `new_key` already holds some data.
+pub fn rename_data(old_key: &str, new_key: &str) -> WasmResult<bool> {
+    let buf = load_data(old_key);
+    if buf.is_empty() || old_key == new_key {
+        return Ok(!buf.is_empty());
+    }
+    if !load_data(new_key).is_empty() {
+        return Err(WasmError::new("data key already exists"));
+    }
+    store_data(new_key, &buf);
+    remove_data(old_key);
+    Ok(true)
+}
+
 /// Load an asset from the given `account-id` as byte array.
 /// The `asset_id` key is the current account id (owner)
 pub fn load_asset(id: &str) -> Vec<u8> {
@@ -202,6 +867,46 @@ pub fn remove_asset(id: &str) {
     unsafe { hf_remove_asset(id_addr, id.len() as i32) };
 }
 
+/// Verify the signature of a domain-separated message.
+///
+/// The signature shall be computed over the digest value produced by
+/// `hash::domain_digest`, thus a signature for a domain can't be replayed
+/// within another one.
+pub fn verify_domain_signed(pk: &PublicKey, domain: &str, message: &[u8], sign: &[u8]) -> bool {
+    let digest = domain_digest(domain, message);
+    verify(pk, digest.digest(), sign)
+}
+
+/// Verify the signature of the given data by the given pk without the host.
+///
+/// The ECDSA signatures are in the fixed `r || s` form, the data is hashed with
+/// SHA-256 for P-256 keys and with SHA-384 for P-384 keys. Public keys are SEC1
+/// encoded points. Ed25519 keys are not supported, their signatures are
+/// rejected. Available only on not wasm environments, e.g. to validate
+/// signing fixtures within the tests.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn verify_local(pk: &PublicKey, data: &[u8], sign: &[u8]) -> bool {
+    use p256::ecdsa::signature::Verifier;
+
+    let pk = match pk {
+        PublicKey::Ecdsa(pk) => pk,
+        PublicKey::Ed25519(_) => return false,
+    };
+    let res = match pk.curve_id {
+        crate::ecdsa::CurveId::Secp256R1 => p256::ecdsa::VerifyingKey::from_sec1_bytes(&pk.value)
+            .and_then(|key| {
+                let sign = p256::ecdsa::Signature::from_slice(sign)?;
+                key.verify(data, &sign)
+            }),
+        crate::ecdsa::CurveId::Secp384R1 => p384::ecdsa::VerifyingKey::from_sec1_bytes(&pk.value)
+            .and_then(|key| {
+                let sign = p384::ecdsa::Signature::from_slice(sign)?;
+                key.verify(data, &sign)
+            }),
+    };
+    res.is_ok()
+}
+
 /// Verify the signature of the given data by the given pk and algorithm
 pub fn verify(pk: &PublicKey, data: &[u8], sign: &[u8]) -> bool {
     let pk = match rmp_serialize(&pk) {
@@ -224,11 +929,108 @@ pub fn verify(pk: &PublicKey, data: &[u8], sign: &[u8]) -> bool {
     }
 }
 
-/// Calculate a random number for the blockchain
+/// Domain of the delegation signatures.
+const DELEGATION_DOMAIN: &str = "delegation";
+
+/// Link of a delegation chain.
+///
+/// The delegator authorizes the delegate by signing the `delegation_message`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DelegationLink {
+    /// Public key of the delegator.
+    pub delegator_pk: PublicKey,
+    /// Account id of the delegate.
+    pub delegate: String,
+    /// Delegator signature of the delegation message.
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+}
+
+/// Message authorizing the `delegate` on behalf of the `delegator` account.
+///
+/// The message is the MessagePack encoding of the `(delegator, delegate)`
+/// account ids pair. The delegator signs its `hash::domain_digest` within the
+/// `"delegation"` domain, see `verify_domain_signed`.
+pub fn delegation_message(delegator: &str, delegate: &str) -> WasmResult<Vec<u8>> {
+    rmp_serialize(&(delegator, delegate))
+}
+
+/// Verify a chain of delegated authorizations and return the last delegate.
+///
+/// The delegator of the first link shall be the trusted `root` account. Each
+/// link shall be signed by the delegator and, except for the first one, the
+/// delegator shall be the delegate of the previous link.
+///
+/// The trusted `root` is required on purpose, in addition to the links: the
+/// links alone only prove to be consistent with each other, thus without it
+/// any account could self-issue a valid chain.
+pub fn verify_delegation_chain(root: &str, links: &[DelegationLink]) -> WasmResult<String> {
+    let first = links
+        .first()
+        .ok_or_else(|| WasmError::new("empty delegation chain"))?;
+    if first.delegator_pk.account_id()? != root {
+        return Err(WasmError::new("untrusted delegation root"));
+    }
+    let mut delegate: Option<&str> = None;
+    for (i, link) in links.iter().enumerate() {
+        let delegator = link.delegator_pk.account_id()?;
+        if let Some(prev_delegate) = delegate {
+            if delegator != prev_delegate {
+                return Err(WasmError::new(&format!("broken delegation at link {}", i)));
+            }
+        }
+        let message = delegation_message(&delegator, &link.delegate)?;
+        if !verify_domain_signed(
+            &link.delegator_pk,
+            DELEGATION_DOMAIN,
+            &message,
+            &link.signature,
+        ) {
+            return Err(WasmError::new(&format!(
+                "invalid delegation signature at link {}",
+                i
+            )));
+        }
+        delegate = Some(link.delegate.as_str());
+    }
+    delegate
+        .map(|delegate| delegate.to_owned())
+        .ok_or_else(|| WasmError::new("empty delegation chain"))
+}
+
+/// Calculate a random number for the blockchain, in the range `[0, max)`.
+///
+/// The value is deterministic across the nodes, as required by the consensus,
+/// thus it is predictable and NOT cryptographically secure: don't use it for
+/// secrets or where the outcome must not be anticipated by the callers.
 pub fn drand(max: u64) -> u64 {
     unsafe { hf_drand(max) }
 }
 
+/// Select an index with a probability proportional to its weight.
+///
+/// The draw is performed via `drand` over the sum of all the weights, then
+/// the index whose cumulative weight range contains the draw is returned.
+pub fn drand_weighted_select(weights: &[u64]) -> WasmResult<usize> {
+    let total = weights
+        .iter()
+        .try_fold(0u64, |acc, weight| acc.checked_add(*weight))
+        .ok_or_else(|| WasmError::new("weights overflow"))?;
+    if total == 0 {
+        return Err(WasmError::new("nothing to select"));
+    }
+
+    let draw = drand(total);
+    let mut cumulative = 0;
+    for (i, weight) in weights.iter().enumerate() {
+        cumulative += weight;
+        if draw < cumulative {
+            return Ok(i);
+        }
+    }
+    Err(WasmError::new("random draw out of range"))
+}
+
 /// Get the next block timestamp
 pub fn get_block_time() -> u64 {
     unsafe { hf_get_block_time() }
@@ -241,33 +1043,175 @@ pub fn sha256(data: &[u8]) -> Vec<u8> {
     slice_from_wslice(wslice).to_vec()
 }
 
-/// Call a method of an arbitrary smart contract passing the data as argument
-pub fn call(account: &str, method: &str, data: &[u8]) -> WasmResult<Vec<u8>> {
-    let account_addr = slice_to_mem(account.as_bytes());
-    let method_addr = slice_to_mem(method.as_bytes());
-    let data_addr = slice_to_mem(data);
-    let wslice = unsafe {
-        hf_call(
-            account_addr,
-            account.len() as i32,
-            method_addr,
-            method.len() as i32,
-            data_addr,
-            data.len() as i32,
-        )
-    };
-    let buf = slice_from_wslice(wslice);
-    let result: AppOutput = rmp_deserialize(buf)?;
-    match result.success {
+/// SHA-256 of the fields, each one prefixed by its big-endian `u32` length
+/// to prevent ambiguities between different fields splits.
+fn hash_fields(fields: &[&[u8]]) -> Hash {
+    let mut buf = Vec::new();
+    for field in fields {
+        buf.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        buf.extend_from_slice(field);
+    }
+    Hash::from_data(HashAlgorithm::Sha256, &buf)
+}
+
+/// Hash of a method invocation, computed over the caller, the method and
+/// the arguments.
+///
+/// Can be used as idempotency key or audit reference for a specific call.
+pub fn hash_invocation(ctx: &AppContext, args: &[u8]) -> Hash {
+    hash_fields(&[ctx.caller.as_bytes(), ctx.method.as_bytes(), args])
+}
+
+/// Order identifier, computed over the account, the nonce and the order parameters.
+pub fn hash_order(account: &str, nonce: u64, params: &[u8]) -> Hash {
+    hash_fields(&[account.as_bytes(), &nonce.to_be_bytes(), params])
+}
+
+/// Commitment to a value, to be revealed later along with the nonce.
+///
+/// The nonce shall be random and kept secret until the reveal, otherwise
+/// the committed value can be guessed.
+pub fn commitment(value: &[u8], nonce: &[u8]) -> Hash {
+    hash_fields(&[value, nonce])
+}
+
+/// Verify the revealed amount of a sealed bid against its commitment.
+///
+/// The commitment shall be computed over the big-endian bytes of the amount.
+pub fn verify_sealed_amount(commitment: &Hash, amount: u64, nonce: &[u8]) -> WasmResult<()> {
+    match self::commitment(&amount.to_be_bytes(), nonce) == *commitment {
+        true => Ok(()),
+        false => Err(WasmError::new("commitment mismatch")),
+    }
+}
+
+/// Append data to the hash chain whose root is stored under the given key.
+///
+/// The new root is computed as `sha256(prev_root || data)`, where `prev_root`
+/// is the multihash of the previous root (empty for the first element).
+pub fn hash_chain_append(key: &str, data: &[u8]) -> Hash {
+    let mut buf = load_data(key);
+    buf.extend_from_slice(data);
+    let root = Hash::from_data(HashAlgorithm::Sha256, &buf);
+    store_data(key, root.as_bytes());
+    root
+}
+
+/// Data key holding the transfers audit hash chain root.
+pub const AUDIT_ROOT_KEY: &str = "__sdk:audit_root";
+
+/// Record a transfer in the audit hash chain and return the new root.
+///
+/// Each recorded transfer updates the root, thus the root is a proof of the
+/// whole sequence of recorded transfers.
+pub fn record_transfer_audit(from: &str, to: &str, units: u64) -> WasmResult<Hash> {
+    let buf = rmp_serialize(&AssetTransferArgs {
+        from,
+        to,
+        units,
+        data: None,
+    })?;
+    Ok(hash_chain_append(AUDIT_ROOT_KEY, &buf))
+}
+
+thread_local! {
+    static AUDIT_GROUP: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Audit log entry of an operation performed within an audit group.
+///
+/// The `from` field is empty for credits and the `to` field is empty for debits.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AuditGroupEntry<'a> {
+    pub group: &'a str,
+    pub operation: &'a str,
+    pub from: &'a str,
+    pub to: &'a str,
+    pub asset: &'a str,
+    pub units: u64,
+}
+
+/// Run `f` tagging the transfers, credits and debits it performs with the group name.
+///
+/// Each tagged operation is recorded in the audit hash chain and emitted as
+/// an `audit` event carrying an `AuditGroupEntry`, this allows to correlate
+/// the legs of a multi-step operation. Groups can be nested, the innermost
+/// one is used.
+pub fn with_audit_group<F, T>(group_name: &str, f: F) -> WasmResult<T>
+where
+    F: FnOnce() -> WasmResult<T>,
+{
+    let prev = AUDIT_GROUP.with(|group| group.replace(Some(group_name.to_owned())));
+    let res = f();
+    AUDIT_GROUP.with(|group| *group.borrow_mut() = prev);
+    res
+}
+
+fn record_group_audit(
+    operation: &str,
+    from: &str,
+    to: &str,
+    asset: &str,
+    units: u64,
+) -> WasmResult<()> {
+    let group = match AUDIT_GROUP.with(|group| group.borrow().clone()) {
+        Some(group) => group,
+        None => return Ok(()),
+    };
+    let buf = rmp_serialize_named(&AuditGroupEntry {
+        group: &group,
+        operation,
+        from,
+        to,
+        asset,
+        units,
+    })?;
+    hash_chain_append(AUDIT_ROOT_KEY, &buf);
+    emit_data("audit", &buf);
+    Ok(())
+}
+
+/// Call a method of an arbitrary smart contract passing the data as argument
+pub fn call(account: &str, method: &str, data: &[u8]) -> WasmResult<Vec<u8>> {
+    let account_addr = slice_to_mem(account.as_bytes());
+    let method_addr = slice_to_mem(method.as_bytes());
+    let data_addr = slice_to_mem(data);
+    let wslice = unsafe {
+        hf_call(
+            account_addr,
+            account.len() as i32,
+            method_addr,
+            method.len() as i32,
+            data_addr,
+            data.len() as i32,
+        )
+    };
+    let buf = slice_from_wslice(wslice);
+    let result: AppOutput = rmp_deserialize(buf)?;
+    match result.success {
         true => Ok(result.data.to_owned()),
-        false => {
-            let msg = String::from_utf8_lossy(result.data);
-            Err(WasmError::new(&msg))
-        }
+        false => Err(WasmError::from_app_output(&result)),
     }
 }
 
+/// Call a method of an arbitrary smart contract with typed arguments and result.
+///
+/// The arguments are serialized with `rmp_serialize` and the returned data is
+/// deserialized into the result type.
+pub fn call_typed<T: Serialize, U: DeserializeOwned>(
+    account: &str,
+    method: &str,
+    args: &T,
+) -> WasmResult<U> {
+    let data = rmp_serialize(args)?;
+    let buf = call(account, method, &data)?;
+    rmp_deserialize(&buf)
+}
+
 /// Call a method of an arbitrary smart contract passing the data as argument
+///
+/// The `contract` is the expected contract hash (multihash bytes) bound to the
+/// account, the host rejects the call if the bound contract differs.
 pub fn s_call(account: &str, contract: &[u8], method: &str, data: &[u8]) -> WasmResult<Vec<u8>> {
     let account_addr = slice_to_mem(account.as_bytes());
     let contract_addr = slice_to_mem(contract);
@@ -289,10 +1233,7 @@ pub fn s_call(account: &str, contract: &[u8], method: &str, data: &[u8]) -> Wasm
     let result: AppOutput = rmp_deserialize(buf)?;
     match result.success {
         true => Ok(result.data.to_owned()),
-        false => {
-            let msg = String::from_utf8_lossy(result.data);
-            Err(WasmError::new(&msg))
-        }
+        false => Err(WasmError::from_app_output(&result)),
     }
 }
 
@@ -303,17 +1244,102 @@ pub fn asset_balance(asset: &str) -> WasmResult<u64> {
     call(asset, "balance", &[]).map(|buf| rmp_deserialize(&buf).unwrap_or_default())
 }
 
+/// Check that the current account holds at least `min` units of the given asset.
+///
+/// The TAI `balance` method takes no arguments and reports the balance of the
+/// calling account, thus other accounts cannot be checked.
+/// Fails with `insufficient balance` when the balance is below `min`.
+pub fn require_min_balance(asset: &str, min: u64) -> WasmResult<()> {
+    let units: u64 = call(asset, "balance", &[]).and_then(|buf| rmp_deserialize(&buf))?;
+    match units < min {
+        true => Err(WasmError::new("insufficient balance")),
+        false => Ok(()),
+    }
+}
+
+/// Check that the caller staked at least `min` units of the asset.
+///
+/// The stake is the caller internal balance of the asset (see `credit`), the
+/// TAI `balance` method only reports the balance of the calling account.
+/// Fails with `insufficient stake` when the stake is below `min`.
+pub fn require_min_stake(ctx: &AppContext, asset: &str, min: u64) -> WasmResult<()> {
+    match internal_balance(ctx.caller, asset)? < min {
+        true => Err(WasmError::new("insufficient stake")),
+        false => Ok(()),
+    }
+}
+
+/// Get the asset descriptor, units and lock, held by an account.
+///
+/// This is an helper function over the lower level `call(asset_id, "state", account)`.
+pub fn asset_state(account: &str, asset: &str) -> WasmResult<Asset> {
+    let data = rmp_serialize(&account)?;
+    call(asset, "state", &data).and_then(|buf| rmp_deserialize(&buf))
+}
+
+/// Get units and lock status held by an account for each of the given assets.
+///
+/// Returns a vector of `(asset, units, is_locked)` tuples.
+pub fn asset_locked_summary(
+    account: &str,
+    assets: &[&str],
+) -> WasmResult<Vec<(String, u64, bool)>> {
+    assets
+        .iter()
+        .map(|asset| {
+            asset_state(account, asset)
+                .map(|state| (asset.to_string(), state.units, state.lock.is_some()))
+        })
+        .collect()
+}
+
 /// Transfer an amount of asset units to a destination account.
 ///
 /// This is an helper function over the lower level `call(asset_id, "transfer", args)`.
 pub fn asset_transfer(from: &str, to: &str, asset: &str, units: u64) -> WasmResult<()> {
+    transfer_with_data(from, to, asset, units, None)
+}
+
+fn transfer_with_data(
+    from: &str,
+    to: &str,
+    asset: &str,
+    units: u64,
+    data: Option<Vec<u8>>,
+) -> WasmResult<()> {
+    // Debug assist: report a clearer error when the asset contract is missing.
+    #[cfg(not(target_arch = "wasm32"))]
+    if !is_callable(asset, "transfer") {
+        return Err(WasmError::new(&format!(
+            "asset contract not found: {}",
+            asset
+        )));
+    }
+
     let data = rmp_serialize_named(&AssetTransferArgs {
         from,
         to,
         units,
-        data: None,
+        data,
     })?;
-    call(asset, "transfer", &data).map(|_buf| ())
+    call(asset, "transfer", &data)?;
+    record_group_audit("transfer", from, to, asset, units)
+}
+
+/// Transfer an amount of asset units only if the condition holds.
+///
+/// Returns whether the transfer has been performed.
+pub fn transfer_if(
+    from: &str,
+    to: &str,
+    asset: &str,
+    units: u64,
+    cond: impl FnOnce() -> WasmResult<bool>,
+) -> WasmResult<bool> {
+    match cond()? {
+        true => asset_transfer(from, to, asset, units).map(|_| true),
+        false => Ok(false),
+    }
 }
 
 /// Transfer an amount of asset units to a destination account with accessory data.
@@ -326,19 +1352,11 @@ pub fn adv_asset_transfer(
     units: u64,
     data: &[u8],
 ) -> WasmResult<()> {
-    let data = if data.is_empty() {
-        None
-    } else {
-        Some(data.to_vec())
+    let data = match data.is_empty() {
+        true => None,
+        false => Some(data.to_vec()),
     };
-
-    let data = rmp_serialize_named(&AssetTransferArgs {
-        from,
-        to,
-        units,
-        data,
-    })?;
-    call(asset, "transfer", &data).map(|_buf| ())
+    transfer_with_data(from, to, asset, units, data)
 }
 
 /// Lock/Unlock the asset.
@@ -356,8 +1374,1301 @@ pub fn load_asset_typed<T: DeserializeOwned + Default>(id: &str) -> T {
     rmp_deserialize(&buf).unwrap_or_default()
 }
 
+/// Load asset with the given asset id from the current account
+/// and tries to convert it into a type.
+///
+/// Returns `None` if the asset is absent, this allows to distinguish an
+/// uninitialized asset from a zero one. Fails on corrupt data.
+pub fn try_load_asset_typed<T: DeserializeOwned>(id: &str) -> WasmResult<Option<T>> {
+    let buf = load_asset(id);
+    match buf.is_empty() {
+        true => Ok(None),
+        false => rmp_deserialize(&buf).map(Some),
+    }
+}
+
 /// Store the typed asset with the given asset id in the current account.
 pub fn store_asset_typed<T: Serialize>(id: &str, value: T) {
     let buf = rmp_serialize(&value).unwrap();
     store_asset(id, &buf);
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        not_wasm,
+        tai::{LockPrivilege, LockType},
+    };
+
+    const OWNER: &str = "QmYHnEQLdf5h7KYbjFPuHSRk2SPgdXrJWFh5W696HPfq7i";
+    const CALLER: &str = "QmTeNPcQnoxinb9bcQhuFxteTQ4sN3qSWJNoHjgEr84zNY";
+    const ASSET: &str = "QmZKrfoq8ZtkH445373qFQo8mJUEc1jx1avMLY9JRTMJMD";
+    const ASSET2: &str = "QmSCRCPFznxEX6S316M4yVmxdxPB6XN63ob2LjFYkP6MLq";
+
+    #[test]
+    fn weighted_select_first() {
+        not_wasm::set_drand_value(0);
+
+        let res = drand_weighted_select(&[6, 4]).unwrap();
+
+        assert_eq!(res, 0);
+    }
+
+    #[test]
+    fn weighted_select_boundary() {
+        not_wasm::set_drand_sequence(vec![4, 5, 9]);
+
+        let res: Vec<usize> = (0..3)
+            .map(|_| drand_weighted_select(&[5, 5]).unwrap())
+            .collect();
+
+        assert_eq!(res, vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn weighted_select_skips_zero_weights() {
+        not_wasm::set_drand_sequence(vec![0, 1, 8, 9]);
+
+        let res: Vec<usize> = (0..4)
+            .map(|_| drand_weighted_select(&[1, 0, 8, 1]).unwrap())
+            .collect();
+
+        assert_eq!(res, vec![0, 2, 2, 3]);
+    }
+
+    #[test]
+    fn weighted_select_nothing_to_select() {
+        let err = drand_weighted_select(&[0, 0]).unwrap_err();
+
+        assert_eq!(err.to_string(), "nothing to select");
+    }
+
+    fn corrupted_balance(_ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
+        Ok(PackedValue(vec![0xc1]))
+    }
+
+    #[test]
+    fn min_balance_sufficient() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "balance", not_wasm::asset_balance);
+        not_wasm::set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+
+        let res = require_min_balance(ASSET, 10);
+
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn min_balance_insufficient() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "balance", not_wasm::asset_balance);
+        not_wasm::set_account_asset_gen(OWNER, ASSET, Asset::new(9));
+        not_wasm::set_account_asset_gen(CALLER, ASSET, Asset::new(100));
+
+        let err = require_min_balance(ASSET, 10).unwrap_err();
+
+        assert_eq!(err.to_string(), "insufficient balance");
+    }
+
+    #[test]
+    fn min_balance_corrupted() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "balance", corrupted_balance);
+
+        let err = require_min_balance(ASSET, 10).unwrap_err();
+
+        assert_ne!(err.to_string(), "insufficient balance");
+    }
+
+    #[test]
+    fn min_stake_sufficient() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        credit(CALLER, ASSET, 10).unwrap();
+
+        let res = require_min_stake(&ctx, ASSET, 10);
+
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn min_stake_insufficient() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        credit(CALLER, ASSET, 9).unwrap();
+        credit(OWNER, ASSET, 100).unwrap();
+
+        let err = require_min_stake(&ctx, ASSET, 10).unwrap_err();
+
+        assert_eq!(err.to_string(), "insufficient stake");
+    }
+
+    #[test]
+    fn min_stake_corrupted_balance() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        store_data(&balance_key(CALLER, ASSET), &[0xc1]);
+
+        let err = require_min_stake(&ctx, ASSET, 10).unwrap_err();
+
+        assert_ne!(err.to_string(), "insufficient stake");
+    }
+
+    #[test]
+    fn numeric_range_with_gaps() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        store_numeric("item:", 1, &"a").unwrap();
+        store_numeric("item:", 3, &"b").unwrap();
+        store_numeric("item:", 4, &"c").unwrap();
+
+        let all: Vec<(u64, String)> = iter_numeric_range("item:", 0, 10).unwrap();
+        let some: Vec<(u64, String)> = iter_numeric_range("item:", 2, 3).unwrap();
+
+        assert_eq!(
+            all,
+            vec![
+                (1, "a".to_string()),
+                (3, "b".to_string()),
+                (4, "c".to_string())
+            ]
+        );
+        assert_eq!(some, vec![(3, "b".to_string())]);
+        assert!(not_wasm::get_account_keys(OWNER).contains(&format!("item:{:020}", 1)));
+    }
+
+    #[test]
+    fn store_data_safe_normal_key() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        store_data_safe("config", &[1, 2, 3]).unwrap();
+
+        assert_eq!(load_data("config"), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn store_data_safe_reserved_key() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let err = store_data_safe("__sdk:nonce", &[1, 2, 3]).unwrap_err();
+
+        assert_eq!(err.to_string(), "reserved data key");
+        assert!(load_data("__sdk:nonce").is_empty());
+    }
+
+    #[test]
+    fn data_set_members() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let set = DataSet::new("members");
+
+        set.insert("alice");
+        set.insert("bob");
+        set.remove("alice");
+
+        assert!(!set.contains("alice"));
+        assert!(set.contains("bob"));
+        assert_eq!(set.members().unwrap(), vec!["bob".to_string()]);
+    }
+
+    #[test]
+    fn caller_policy_enforcement() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let policy = CallerPolicy::new("committee");
+
+        let before = policy.enforce(&ctx);
+        policy.allow(CALLER);
+        let allowed = policy.enforce(&ctx);
+        policy.revoke(CALLER);
+        let revoked = policy.enforce(&ctx);
+
+        assert_eq!(before.unwrap_err().to_string(), "caller not allowed");
+        assert!(allowed.is_ok());
+        assert_eq!(revoked.unwrap_err().to_string(), "caller not allowed");
+    }
+
+    #[test]
+    fn invocation_hash_deterministic() {
+        let mut ctx = not_wasm::create_app_context(OWNER, CALLER);
+        ctx.method = "transfer";
+
+        let hash1 = hash_invocation(&ctx, &[1, 2, 3]);
+        let hash2 = hash_invocation(&ctx, &[1, 2, 3]);
+
+        assert_eq!(hash1, hash2);
+    }
+
+    #[test]
+    fn invocation_hash_components() {
+        let mut ctx = not_wasm::create_app_context(OWNER, CALLER);
+        ctx.method = "transfer";
+        let hash = hash_invocation(&ctx, &[1, 2, 3]);
+
+        let other_args = hash_invocation(&ctx, &[1, 2, 4]);
+        ctx.method = "balance";
+        let other_method = hash_invocation(&ctx, &[1, 2, 3]);
+        ctx.method = "transfer";
+        ctx.caller = OWNER;
+        let other_caller = hash_invocation(&ctx, &[1, 2, 3]);
+
+        assert_ne!(hash, other_args);
+        assert_ne!(hash, other_method);
+        assert_ne!(hash, other_caller);
+    }
+
+    #[test]
+    fn order_hash_deterministic() {
+        let hash = hash_order(CALLER, 1, &[1, 2, 3]);
+
+        let same = hash_order(CALLER, 1, &[1, 2, 3]);
+
+        assert_eq!(hash, same);
+    }
+
+    #[test]
+    fn order_hash_inputs_sensitivity() {
+        let hash = hash_order(CALLER, 1, &[1, 2, 3]);
+
+        let other_account = hash_order(OWNER, 1, &[1, 2, 3]);
+        let other_nonce = hash_order(CALLER, 2, &[1, 2, 3]);
+        let other_params = hash_order(CALLER, 1, &[1, 2, 4]);
+
+        assert_ne!(hash, other_account);
+        assert_ne!(hash, other_nonce);
+        assert_ne!(hash, other_params);
+    }
+
+    #[test]
+    fn progress_event_payload() {
+        emit_progress("import", 3, 10);
+
+        let events = not_wasm::get_emitted_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, "progress");
+        let event: ProgressEvent = rmp_deserialize(&events[0].1).unwrap();
+        assert_eq!(
+            event,
+            ProgressEvent {
+                operation: "import",
+                done: 3,
+                total: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn allowance_response_after_spend() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        approve_allowance(CALLER, ASSET, 100).unwrap();
+        spend_allowance(CALLER, ASSET, 30).unwrap();
+
+        let response = allowance_response(CALLER, ASSET).unwrap();
+
+        assert_eq!(
+            response,
+            value!({
+                "owner": CALLER,
+                "spender": ASSET,
+                "remaining": 70u64,
+            })
+        );
+    }
+
+    #[test]
+    fn allowance_overspend() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        approve_allowance(CALLER, ASSET, 10).unwrap();
+
+        let err = spend_allowance(CALLER, ASSET, 11).unwrap_err();
+
+        assert_eq!(err.to_string(), "allowance exceeded");
+        assert_eq!(allowance(CALLER, ASSET).unwrap(), 10);
+    }
+
+    #[test]
+    fn load_data_typed_opt_absent() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let value = load_data_typed_opt::<u64>("config").unwrap();
+
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn load_data_typed_opt_present() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_account_data(OWNER, "config", &rmp_serialize(&0u64).unwrap());
+
+        let value = load_data_typed_opt::<u64>("config").unwrap();
+
+        assert_eq!(value, Some(0));
+    }
+
+    #[test]
+    fn load_data_typed_opt_corrupt() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_account_data(OWNER, "config", &[0xc1]);
+
+        let res = load_data_typed_opt::<u64>("config");
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn data_key_diff_partition() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_account_data(OWNER, "config", &[1]);
+        not_wasm::set_account_data(OWNER, "stale_b", &[1]);
+        not_wasm::set_account_data(OWNER, "stale_a", &[1]);
+        not_wasm::set_account_data(OWNER, "__sdk:internal", &[1]);
+
+        let (missing, extra) = data_key_diff(&["config", "owner", "fee"]).unwrap();
+
+        assert_eq!(missing, vec!["owner", "fee"]);
+        assert_eq!(extra, vec!["stale_a", "stale_b"]);
+    }
+
+    #[test]
+    fn next_sequence_in_order() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let res: Vec<_> = (1..=3)
+            .map(|seq| require_next_sequence("feed", seq))
+            .collect();
+
+        assert!(res.iter().all(|res| res.is_ok()));
+    }
+
+    #[test]
+    fn next_sequence_duplicate() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        require_next_sequence("feed", 1).unwrap();
+
+        let err = require_next_sequence("feed", 1).unwrap_err();
+
+        assert_eq!(err.to_string(), "unexpected sequence number");
+    }
+
+    #[test]
+    fn next_sequence_skipped() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        require_next_sequence("feed", 1).unwrap();
+
+        let err = require_next_sequence("feed", 3).unwrap_err();
+
+        assert_eq!(err.to_string(), "unexpected sequence number");
+        assert!(require_next_sequence("feed", 2).is_ok());
+    }
+
+    #[test]
+    fn asset_transfer_unregistered_asset() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let err = asset_transfer(OWNER, CALLER, ASSET, 1).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!("asset contract not found: {}", ASSET)
+        );
+    }
+
+    #[test]
+    fn roles_grant_and_revoke() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let before = Roles::require_role(&ctx, "minter");
+        Roles::grant("minter", CALLER);
+        let granted = Roles::require_role(&ctx, "minter");
+        let other_role = Roles::require_role(&ctx, "pauser");
+        Roles::revoke("minter", CALLER);
+        let revoked = Roles::require_role(&ctx, "minter");
+
+        assert_eq!(before.unwrap_err().to_string(), "missing role: minter");
+        assert!(granted.is_ok());
+        assert_eq!(other_role.unwrap_err().to_string(), "missing role: pauser");
+        assert_eq!(revoked.unwrap_err().to_string(), "missing role: minter");
+        assert!(!Roles::has_role("minter", CALLER));
+    }
+
+    fn guarded_method(_ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
+        when_not_paused!();
+        store_data("guarded", &[1]);
+        Ok(PackedValue::default())
+    }
+
+    #[test]
+    fn pausable_guarded_method() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let call_guarded = || {
+            guarded_method(
+                not_wasm::create_app_context(OWNER, CALLER),
+                PackedValue::default(),
+            )
+        };
+
+        let unpaused = call_guarded();
+        not_wasm::set_account_data(OWNER, "guarded", &[]);
+        Pausable::pause();
+        let paused = call_guarded();
+        let stored_when_paused = load_data("guarded");
+        Pausable::unpause();
+        let resumed = call_guarded();
+
+        assert!(unpaused.is_ok());
+        assert_eq!(paused.unwrap_err().to_string(), "contract is paused");
+        assert!(stored_when_paused.is_empty());
+        assert!(resumed.is_ok());
+        assert!(!Pausable::is_paused());
+    }
+
+    #[test]
+    fn bitset_across_bytes() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let mut flags = BitSet::load("flags");
+
+        flags.set(0);
+        flags.set(7);
+        flags.set(8);
+        flags.set(17);
+        flags.clear(7);
+        flags.clear(100);
+
+        assert!(flags.get(0));
+        assert!(!flags.get(7));
+        assert!(flags.get(8));
+        assert!(flags.get(17));
+        assert!(!flags.get(1000));
+    }
+
+    #[test]
+    fn bitset_persistence() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let mut flags = BitSet::load("flags");
+        flags.set(3);
+        flags.set(9);
+        flags.store();
+
+        let mut reloaded = BitSet::load("flags");
+        reloaded.clear(9);
+        reloaded.store();
+
+        assert_eq!(not_wasm::get_account_data(OWNER, "flags"), vec![0x08]);
+        assert!(BitSet::load("flags").get(3));
+        assert!(!BitSet::load("flags").get(9));
+    }
+
+    #[test]
+    fn require_bound_contract() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let hash = Hash::from_data(HashAlgorithm::Sha256, b"contract");
+        let other = Hash::from_data(HashAlgorithm::Sha256, b"other contract");
+        not_wasm::set_contract_hash(ASSET, hash.as_bytes());
+
+        let matching = require_contract(ASSET, &hash);
+        let mismatching = require_contract(ASSET, &other);
+        let unbound = require_contract(ASSET2, &hash);
+
+        assert!(matching.is_ok());
+        assert_eq!(mismatching.unwrap_err().to_string(), "unexpected contract");
+        assert_eq!(unbound.unwrap_err().to_string(), "unexpected contract");
+    }
+
+    #[test]
+    fn credit_and_debit() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let credited = credit(CALLER, ASSET, 10).unwrap();
+        let debited = debit(CALLER, ASSET, 4).unwrap();
+
+        assert_eq!(credited, 10);
+        assert_eq!(debited, 6);
+        assert_eq!(internal_balance(CALLER, ASSET).unwrap(), 6);
+        assert_eq!(internal_balance(CALLER, ASSET2).unwrap(), 0);
+    }
+
+    #[test]
+    fn debit_underflow() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        credit(CALLER, ASSET, 3).unwrap();
+
+        let err = debit(CALLER, ASSET, 4).unwrap_err();
+
+        assert_eq!(err.to_string(), "insufficient balance");
+        assert_eq!(internal_balance(CALLER, ASSET).unwrap(), 3);
+    }
+
+    #[test]
+    fn credit_overflow() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        credit(CALLER, ASSET, u64::MAX).unwrap();
+
+        let err = credit(CALLER, ASSET, 1).unwrap_err();
+
+        assert_eq!(err.to_string(), "balance overflow");
+    }
+
+    #[test]
+    fn credit_and_debit_events() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        credit(CALLER, ASSET, 10).unwrap();
+        debit(CALLER, ASSET, 4).unwrap();
+
+        let events = not_wasm::get_emitted_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0, "credit");
+        assert_eq!(events[1].0, "debit");
+        let event: BalanceEvent = rmp_deserialize(&events[1].1).unwrap();
+        assert_eq!(
+            event,
+            BalanceEvent {
+                account: CALLER,
+                asset: ASSET,
+                units: 4,
+                balance: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn transfer_if_condition_holds() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "transfer", not_wasm::asset_transfer);
+        not_wasm::set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+
+        let done = transfer_if(OWNER, CALLER, ASSET, 4, || Ok(true)).unwrap();
+
+        assert!(done);
+        assert_eq!(
+            not_wasm::get_account_asset_gen::<Asset>(OWNER, ASSET).units,
+            6
+        );
+        assert_eq!(
+            not_wasm::get_account_asset_gen::<Asset>(CALLER, ASSET).units,
+            4
+        );
+    }
+
+    #[test]
+    fn transfer_if_condition_fails() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "transfer", not_wasm::asset_transfer);
+        not_wasm::set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+
+        let done = transfer_if(OWNER, CALLER, ASSET, 4, || Ok(false)).unwrap();
+        let err = transfer_if(OWNER, CALLER, ASSET, 4, || Err(WasmError::new("no oracle")));
+
+        assert!(!done);
+        assert_eq!(err.unwrap_err().to_string(), "no oracle");
+        assert_eq!(
+            not_wasm::get_account_asset_gen::<Asset>(OWNER, ASSET).units,
+            10
+        );
+    }
+
+    #[test]
+    fn adv_asset_transfer_memo() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "transfer", not_wasm::asset_transfer);
+        not_wasm::set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+
+        adv_asset_transfer(OWNER, CALLER, ASSET, 4, b"memo").unwrap();
+
+        let trace = not_wasm::get_call_trace();
+        let args: AssetTransferArgs = rmp_deserialize(&trace[0].args).unwrap();
+        assert_eq!(args.data, Some(b"memo".to_vec()));
+        assert_eq!(
+            not_wasm::get_account_asset_gen::<Asset>(CALLER, ASSET).units,
+            4
+        );
+    }
+
+    #[test]
+    fn asset_transfer_without_data() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "transfer", not_wasm::asset_transfer);
+        not_wasm::set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+
+        asset_transfer(OWNER, CALLER, ASSET, 4).unwrap();
+
+        let trace = not_wasm::get_call_trace();
+        let args: AssetTransferArgs = rmp_deserialize(&trace[0].args).unwrap();
+        assert_eq!(args.data, None);
+    }
+
+    fn draw_method(_ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue> {
+        let max: u64 = rmp_deserialize(&args)?;
+        rmp_serialize(&drand(max)).map(PackedValue)
+    }
+
+    #[test]
+    fn contract_method_drand() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "draw", draw_method);
+        not_wasm::set_drand_value(7);
+
+        let buf = call(ASSET, "draw", &rmp_serialize(&10u64).unwrap()).unwrap();
+        let clamped = call(ASSET, "draw", &rmp_serialize(&5u64).unwrap()).unwrap();
+
+        assert_eq!(rmp_deserialize::<u64>(&buf).unwrap(), 7);
+        assert_eq!(rmp_deserialize::<u64>(&clamped).unwrap(), 4);
+    }
+
+    #[test]
+    fn s_call_bound_contract() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "draw", draw_method);
+        let hash = Hash::from_data(HashAlgorithm::Sha256, b"contract");
+        let contract = hash.as_bytes();
+        not_wasm::set_contract_hash(ASSET, contract);
+
+        let res = s_call(ASSET, contract, "draw", &rmp_serialize(&10u64).unwrap());
+
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn s_call_incompatible_contract() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "draw", draw_method);
+        let hash = Hash::from_data(HashAlgorithm::Sha256, b"contract");
+        let other = Hash::from_data(HashAlgorithm::Sha256, b"other contract");
+        not_wasm::set_contract_hash(ASSET, hash.as_bytes());
+
+        let err = s_call(ASSET, other.as_bytes(), "draw", &[]).unwrap_err();
+
+        assert_eq!(err.to_string(), "incompatible contract app");
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Quote {
+        asset: String,
+        units: u64,
+    }
+
+    fn double_quote_method(_ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue> {
+        let mut quote: Quote = rmp_deserialize(&args)?;
+        quote.units *= 2;
+        rmp_serialize(&quote).map(PackedValue)
+    }
+
+    #[test]
+    fn call_typed_roundtrip() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET2, "double", double_quote_method);
+        let quote = Quote {
+            asset: ASSET.to_string(),
+            units: 21,
+        };
+
+        let res: Quote = call_typed(ASSET2, "double", &quote).unwrap();
+
+        assert_eq!(
+            res,
+            Quote {
+                asset: ASSET.to_string(),
+                units: 42,
+            }
+        );
+    }
+
+    #[test]
+    fn call_typed_remote_error() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET2, "double", double_quote_method);
+
+        let err = call_typed::<_, Quote>(ASSET2, "double", &42u64).unwrap_err();
+
+        assert_eq!(err.to_string(), "deserialization failure");
+    }
+
+    #[test]
+    fn emit_typed_event() {
+        let quote = Quote {
+            asset: ASSET.to_string(),
+            units: 42,
+        };
+
+        emit("quote", &quote).unwrap();
+
+        let emitted = not_wasm::get_emitted_events();
+        assert_eq!(emitted.len(), 1);
+        assert_eq!(emitted[0].0, "quote");
+        assert_eq!(emitted[0].1, rmp_serialize(&quote).unwrap());
+    }
+
+    fn audit_entries() -> Vec<(String, String, String, u64)> {
+        not_wasm::get_emitted_events()
+            .iter()
+            .filter(|(name, _)| name == "audit")
+            .map(|(_, data)| {
+                let entry: AuditGroupEntry = rmp_deserialize(data).unwrap();
+                (
+                    entry.group.to_string(),
+                    entry.from.to_string(),
+                    entry.to.to_string(),
+                    entry.units,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn audit_group_transfers() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "transfer", not_wasm::asset_transfer);
+        not_wasm::set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+
+        with_audit_group("swap", || {
+            asset_transfer(OWNER, CALLER, ASSET, 4)?;
+            asset_transfer(CALLER, OWNER, ASSET, 1)
+        })
+        .unwrap();
+        asset_transfer(OWNER, CALLER, ASSET, 2).unwrap();
+
+        assert_eq!(
+            audit_entries(),
+            vec![
+                ("swap".to_string(), OWNER.to_string(), CALLER.to_string(), 4),
+                ("swap".to_string(), CALLER.to_string(), OWNER.to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn audit_group_adv_transfer() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "transfer", not_wasm::asset_transfer);
+        not_wasm::set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+
+        with_audit_group("memo", || {
+            adv_asset_transfer(OWNER, CALLER, ASSET, 3, b"memo")
+        })
+        .unwrap();
+
+        assert_eq!(
+            audit_entries(),
+            vec![("memo".to_string(), OWNER.to_string(), CALLER.to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn audit_group_nested_credit() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        with_audit_group("outer", || {
+            credit(OWNER, ASSET, 5)?;
+            with_audit_group("inner", || debit(OWNER, ASSET, 2))
+        })
+        .unwrap();
+
+        assert_eq!(
+            audit_entries(),
+            vec![
+                ("outer".to_string(), "".to_string(), OWNER.to_string(), 5),
+                ("inner".to_string(), OWNER.to_string(), "".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn sealed_amount_reveal() {
+        let sealed = commitment(&1500u64.to_be_bytes(), b"nonce");
+
+        let res = verify_sealed_amount(&sealed, 1500, b"nonce");
+
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn sealed_amount_mismatch() {
+        let sealed = commitment(&1500u64.to_be_bytes(), b"nonce");
+
+        let amount = verify_sealed_amount(&sealed, 1501, b"nonce").unwrap_err();
+        let nonce = verify_sealed_amount(&sealed, 1500, b"other").unwrap_err();
+
+        assert_eq!(amount.to_string(), "commitment mismatch");
+        assert_eq!(nonce.to_string(), "commitment mismatch");
+    }
+
+    const P384_PK_HEX: &str = "04694c843243482ef50caf8c1e89e0c7827c0008d0bdf7355361f8f8a632d261\
+                               1d8384868078c9f1fc4b9fff40085f4c421555985b7b2519fcb0c23b40ee5f7d\
+                               b3ecc9389358fafdeef102a543e39420a218a38ca931e286f47b9c9f8f0b12e740";
+    const P384_SIGN_HEX: &str = "029c52ffb17f48460e29a099be027c0d7f1f730fb078108051f6e2f1673636bc\
+                                 4ce6813eaf0500c7a0e8fe437183d2953ccfd0bd98740df2a099c03406d327ee\
+                                 28c03f176c960b411a32157b5f9c473958a7ed9be06cb594cd94e553a0bd2c4d";
+    const P256_PK_HEX: &str = "04dc35e420d4be7508ebda9fe3e5c9af9689e138e16f3eb8dc8e546cad0a9996\
+                               e5835646aaff8b4ee3627607892237d4a21dbaa3d816df63a26901802e15c7025a";
+    const P256_SIGN_HEX: &str = "5bb096090cc85760aff8c389a035587521991d4931b56f543a99ccea33e6e3c6\
+                                 23033db4a2586383cd05ca1b16ebe8d91351979ab01124ff0f0dad6b3a743ee3";
+
+    #[test]
+    fn verify_local_p384() {
+        let pk = PublicKey::ecdsa(
+            crate::ecdsa::CurveId::Secp384R1,
+            hex::decode(P384_PK_HEX).unwrap(),
+        );
+        let sign = hex::decode(P384_SIGN_HEX).unwrap();
+
+        assert!(verify_local(&pk, b"hello", &sign));
+        assert!(!verify_local(&pk, b"hellO", &sign));
+    }
+
+    #[test]
+    fn verify_local_p256() {
+        let pk = PublicKey::ecdsa(
+            crate::ecdsa::CurveId::Secp256R1,
+            hex::decode(P256_PK_HEX).unwrap(),
+        );
+        let sign = hex::decode(P256_SIGN_HEX).unwrap();
+
+        assert!(verify_local(&pk, b"hello", &sign));
+        assert!(!verify_local(&pk, b"hello", &sign[1..]));
+    }
+
+    #[test]
+    fn verify_with_local_mock() {
+        let pk = PublicKey::ecdsa(
+            crate::ecdsa::CurveId::Secp256R1,
+            hex::decode(P256_PK_HEX).unwrap(),
+        );
+        let mut sign = hex::decode(P256_SIGN_HEX).unwrap();
+        not_wasm::set_local_verify(true);
+
+        let valid = verify(&pk, b"hello", &sign);
+        sign[0] = 1;
+        let tampered = verify(&pk, b"hello", &sign);
+
+        assert!(valid);
+        assert!(!tampered);
+    }
+
+    #[test]
+    fn require_once_per_block_same_block() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_block_time(100);
+
+        require_once_per_block("oracle").unwrap();
+        let err = require_once_per_block("oracle").unwrap_err();
+
+        assert_eq!(err.to_string(), "already called this block");
+    }
+
+    #[test]
+    fn require_once_per_block_next_block() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_block_time(100);
+        require_once_per_block("oracle").unwrap();
+        not_wasm::set_block_time(110);
+
+        let res = require_once_per_block("oracle");
+
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn lease_acquire() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_block_time(100);
+
+        Lease::acquire("oracle", CALLER, 10).unwrap();
+
+        let lease = Lease::get("oracle").unwrap().unwrap();
+        assert_eq!(lease.holder, CALLER);
+        assert_eq!(lease.expires_at, 110);
+    }
+
+    #[test]
+    fn lease_contention() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_block_time(100);
+        Lease::acquire("oracle", CALLER, 10).unwrap();
+        not_wasm::set_block_time(109);
+
+        let acquire = Lease::acquire("oracle", OWNER, 10).unwrap_err();
+        let release = Lease::release("oracle", OWNER).unwrap_err();
+
+        assert_eq!(acquire.to_string(), "resource already leased");
+        assert_eq!(release.to_string(), "resource already leased");
+    }
+
+    #[test]
+    fn lease_acquire_after_expiry() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_block_time(100);
+        Lease::acquire("oracle", CALLER, 10).unwrap();
+        not_wasm::set_block_time(110);
+
+        Lease::acquire("oracle", OWNER, 10).unwrap();
+
+        let lease = Lease::get("oracle").unwrap().unwrap();
+        assert_eq!(lease.holder, OWNER);
+        assert_eq!(lease.expires_at, 120);
+    }
+
+    #[test]
+    fn lease_release() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        Lease::acquire("oracle", CALLER, 10).unwrap();
+
+        Lease::release("oracle", CALLER).unwrap();
+
+        assert_eq!(Lease::get("oracle").unwrap(), None);
+        assert!(Lease::acquire("oracle", OWNER, 10).is_ok());
+    }
+
+    #[test]
+    fn rename_existing_data() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_account_data(OWNER, "old", &[1, 2, 3]);
+
+        let moved = rename_data("old", "new").unwrap();
+
+        assert!(moved);
+        assert_eq!(not_wasm::get_account_data(OWNER, "new"), vec![1, 2, 3]);
+        assert!(not_wasm::get_account_data(OWNER, "old").is_empty());
+    }
+
+    #[test]
+    fn rename_missing_data() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let moved = rename_data("old", "new").unwrap();
+
+        assert!(!moved);
+        assert!(not_wasm::get_account_data(OWNER, "new").is_empty());
+    }
+
+    #[test]
+    fn rename_data_existing_destination() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_account_data(OWNER, "old", &[1]);
+        not_wasm::set_account_data(OWNER, "new", &[2]);
+
+        let err = rename_data("old", "new").unwrap_err();
+
+        assert_eq!(err.to_string(), "data key already exists");
+        assert_eq!(not_wasm::get_account_data(OWNER, "old"), vec![1]);
+        assert_eq!(not_wasm::get_account_data(OWNER, "new"), vec![2]);
+    }
+
+    #[test]
+    fn state_dump_mixed_data() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_account_data(OWNER, "name", &rmp_serialize(&"foo").unwrap());
+        not_wasm::set_account_data(OWNER, "count", &rmp_serialize(&42u64).unwrap());
+        not_wasm::set_account_data(OWNER, "raw", &[1, 2, 3]);
+
+        let state = state_dump().unwrap();
+
+        assert_eq!(get_value_as_str!(state, "name").unwrap(), "foo");
+        assert_eq!(get_value_as_u64!(state, "count").unwrap(), 42);
+        assert_eq!(
+            state.get(&value!("raw")),
+            Some(&Value::Bytes(vec![1, 2, 3]))
+        );
+        assert!(matches!(state, Value::Map(map) if map.len() == 3));
+    }
+
+    #[test]
+    fn emit_batch_order() {
+        let events: &[(&str, &[u8])] = &[("first", &[1]), ("second", &[2, 2]), ("third", &[])];
+
+        emit_batch(events);
+
+        let emitted = not_wasm::get_emitted_events();
+        assert_eq!(emitted.len(), 3);
+        emitted
+            .iter()
+            .zip(events)
+            .for_each(|((name, data), (exp_name, exp_data))| {
+                assert_eq!(name, exp_name);
+                assert_eq!(data, exp_data);
+            });
+    }
+
+    #[test]
+    fn transfer_audit_chain() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let root1 = record_transfer_audit(OWNER, CALLER, 10).unwrap();
+        let root2 = record_transfer_audit(CALLER, OWNER, 5).unwrap();
+
+        let mut buf = root1.as_bytes().to_vec();
+        buf.extend(
+            rmp_serialize(&AssetTransferArgs {
+                from: CALLER,
+                to: OWNER,
+                units: 5,
+                data: None,
+            })
+            .unwrap(),
+        );
+        assert_ne!(root1, root2);
+        assert_eq!(root2, Hash::from_data(HashAlgorithm::Sha256, &buf));
+        assert_eq!(load_data(AUDIT_ROOT_KEY), root2.as_bytes().to_vec());
+    }
+
+    #[test]
+    fn transfer_audit_chain_deterministic() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let root1 = record_transfer_audit(OWNER, CALLER, 10).unwrap();
+
+        let ctx = not_wasm::create_app_context(ASSET, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let root2 = record_transfer_audit(OWNER, CALLER, 10).unwrap();
+
+        assert_eq!(root1, root2);
+    }
+
+    #[test]
+    fn spending_limit_within_period() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let now = get_block_time();
+        SpendingLimit::set(CALLER, ASSET, 100, 3600, now).unwrap();
+
+        SpendingLimit::check_and_record(CALLER, ASSET, 60, now).unwrap();
+        SpendingLimit::check_and_record(CALLER, ASSET, 40, now + 10).unwrap();
+        let err = SpendingLimit::check_and_record(CALLER, ASSET, 1, now + 20).unwrap_err();
+
+        assert_eq!(err.to_string(), "spending limit exceeded");
+        assert_eq!(
+            SpendingLimit::load(CALLER, ASSET).unwrap().unwrap().spent,
+            100
+        );
+    }
+
+    #[test]
+    fn spending_limit_period_rollover() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        let now = get_block_time();
+        SpendingLimit::set(CALLER, ASSET, 100, 3600, now).unwrap();
+        SpendingLimit::check_and_record(CALLER, ASSET, 100, now).unwrap();
+
+        let res = SpendingLimit::check_and_record(CALLER, ASSET, 100, now + 3600);
+
+        assert!(res.is_ok());
+        let limit = SpendingLimit::load(CALLER, ASSET).unwrap().unwrap();
+        assert_eq!(limit.period_start, now + 3600);
+        assert_eq!(limit.spent, 100);
+    }
+
+    #[test]
+    fn spending_limit_not_set() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+
+        let res = SpendingLimit::check_and_record(CALLER, ASSET, u64::MAX, 0);
+
+        assert!(res.is_ok());
+    }
+
+    fn create_test_pk(value: u8) -> PublicKey {
+        PublicKey::Ecdsa(crate::ecdsa::PublicKey {
+            curve_id: crate::ecdsa::CurveId::Secp384R1,
+            value: vec![4, value, value, value],
+        })
+    }
+
+    fn delegation_root() -> String {
+        create_test_pk(1).account_id().unwrap()
+    }
+
+    fn create_delegation_chain() -> Vec<DelegationLink> {
+        let pk_a = create_test_pk(1);
+        let pk_b = create_test_pk(2);
+        let pk_c = create_test_pk(3);
+        vec![
+            DelegationLink {
+                delegator_pk: pk_a,
+                delegate: pk_b.account_id().unwrap(),
+                signature: vec![1],
+            },
+            DelegationLink {
+                delegator_pk: pk_b,
+                delegate: pk_c.account_id().unwrap(),
+                signature: vec![1],
+            },
+            DelegationLink {
+                delegator_pk: pk_c,
+                delegate: CALLER.to_string(),
+                signature: vec![1],
+            },
+        ]
+    }
+
+    // The mocked `verify` uses the first signature byte as result.
+
+    #[test]
+    fn delegation_chain_valid() {
+        let links = create_delegation_chain();
+
+        let res = verify_delegation_chain(&delegation_root(), &links).unwrap();
+
+        assert_eq!(res, CALLER);
+    }
+
+    #[test]
+    fn delegation_chain_broken_signature() {
+        let mut links = create_delegation_chain();
+        links[1].signature = vec![0];
+
+        let err = verify_delegation_chain(&delegation_root(), &links).unwrap_err();
+
+        assert_eq!(err.to_string(), "invalid delegation signature at link 1");
+    }
+
+    #[test]
+    fn delegation_chain_broken_link() {
+        let mut links = create_delegation_chain();
+        links[1].delegator_pk = create_test_pk(4);
+
+        let err = verify_delegation_chain(&delegation_root(), &links).unwrap_err();
+
+        assert_eq!(err.to_string(), "broken delegation at link 1");
+    }
+
+    #[test]
+    fn delegation_chain_untrusted_root() {
+        let mut links = create_delegation_chain();
+        links[0].delegator_pk = create_test_pk(4);
+
+        let err = verify_delegation_chain(&delegation_root(), &links).unwrap_err();
+
+        assert_eq!(err.to_string(), "untrusted delegation root");
+    }
+
+    #[test]
+    fn delegation_chain_empty() {
+        let err = verify_delegation_chain(&delegation_root(), &[]).unwrap_err();
+
+        assert_eq!(err.to_string(), "empty delegation chain");
+    }
+
+    fn create_signed_link(
+        key: &p256::ecdsa::SigningKey,
+        delegate: &str,
+        domain: &str,
+    ) -> DelegationLink {
+        use p256::ecdsa::{signature::Signer, Signature};
+
+        let delegator_pk = PublicKey::ecdsa(
+            crate::ecdsa::CurveId::Secp256R1,
+            key.verifying_key()
+                .to_encoded_point(false)
+                .as_bytes()
+                .to_vec(),
+        );
+        let message = delegation_message(&delegator_pk.account_id().unwrap(), delegate).unwrap();
+        let signature: Signature = key.sign(domain_digest(domain, &message).digest());
+        DelegationLink {
+            delegator_pk,
+            delegate: delegate.to_string(),
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn delegation_chain_signed() {
+        let key = p256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap();
+        let link = create_signed_link(&key, CALLER, "delegation");
+        let root = link.delegator_pk.account_id().unwrap();
+        not_wasm::set_local_verify(true);
+
+        let res = verify_delegation_chain(&root, &[link]).unwrap();
+
+        assert_eq!(res, CALLER);
+    }
+
+    #[test]
+    fn delegation_chain_replayed_signature() {
+        use p256::ecdsa::{signature::Signer, Signature};
+
+        let key = p256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap();
+        let mut raw = create_signed_link(&key, CALLER, "delegation");
+        let raw_signature: Signature = key.sign(CALLER.as_bytes());
+        raw.signature = raw_signature.to_bytes().to_vec();
+        let other_domain = create_signed_link(&key, CALLER, "other");
+        let root = raw.delegator_pk.account_id().unwrap();
+        not_wasm::set_local_verify(true);
+
+        let raw_res = verify_delegation_chain(&root, &[raw]);
+        let other_domain_res = verify_delegation_chain(&root, &[other_domain]);
+
+        assert!(raw_res.is_err());
+        assert!(other_domain_res.is_err());
+    }
+
+    #[test]
+    fn locked_summary() {
+        let ctx = not_wasm::create_app_context(OWNER, CALLER);
+        not_wasm::set_app_ctx(&ctx);
+        not_wasm::set_contract_method(ASSET, "state", not_wasm::asset_state);
+        not_wasm::set_contract_method(ASSET2, "state", not_wasm::asset_state);
+        not_wasm::set_account_asset_gen(CALLER, ASSET, Asset::new(10));
+        not_wasm::seed_locked_asset(CALLER, ASSET2, 5, LockPrivilege::Owner, LockType::Full);
+
+        let summary = asset_locked_summary(CALLER, &[ASSET, ASSET2]).unwrap();
+
+        assert_eq!(
+            summary,
+            vec![
+                (ASSET.to_string(), 10, false),
+                (ASSET2.to_string(), 5, true)
+            ]
+        );
+    }
+}
@@ -28,20 +28,37 @@ mod export;
 // TEMPORARY MODULES :: BEGIN
 pub mod core;
 pub mod ecdsa;
+pub mod ed25519;
 pub mod hash;
 // TEMPORARY MODULES :: END
 
 pub use serde_value::{value, Value};
 
 pub use common::{
-    divide, rmp_deserialize, rmp_serialize, rmp_serialize_named, AppContext, Deserializable,
-    PackedValue, Serializable, WasmError, WasmResult,
+    check_args_depth, derive_subaccount, distribute, divide, fixed_bytes, fixed_bytes_padded,
+    network_params, require_after, require_before, require_duration_max, require_future_timestamp,
+    require_memo_size, require_one_of, respond_with_schema, rmp_deserialize,
+    rmp_deserialize_lenient, rmp_serialize, rmp_serialize_named, str_from_bytes, validate_list,
+    AppContext, ContractMeta, Deserializable, DispatchEntry, NetworkParams, Outcome, PackedValue,
+    SchemaEnvelope, Serializable, SignedCommand, WasmError, WasmResult, ACCOUNT_ID_ALPHABET,
 };
+#[cfg(not(target_arch = "wasm32"))]
+pub use host_wrap::verify_local;
 pub use host_wrap::{
-    adv_asset_transfer, asset_balance, asset_lock, asset_transfer, call, drand, emit_data,
-    get_account_contract, get_block_time, get_data_keys, is_callable, load_asset, load_asset_typed,
-    load_data, log, remove_asset, remove_data, s_call, sha256, store_asset, store_asset_typed,
-    store_data, verify,
+    adv_asset_transfer, allowance, allowance_response, approve_allowance, asset_balance,
+    asset_lock, asset_locked_summary, asset_state, asset_transfer, call, call_typed, commitment,
+    credit, data_key_diff, debit, delegation_message, drand, drand_weighted_select, emit,
+    emit_batch, emit_data, emit_progress, get_account_contract, get_account_contract_hash,
+    get_block_time, get_data_keys, hash_chain_append, hash_invocation, hash_order,
+    internal_balance, is_callable, is_reserved_key, iter_numeric_range, load_asset,
+    load_asset_typed, load_data, load_data_typed, load_data_typed_opt, log, record_transfer_audit,
+    remove_asset, remove_data, rename_data, require_contract, require_min_balance,
+    require_min_stake, require_next_sequence, require_once_per_block, s_call, sha256,
+    spend_allowance, state_dump, store_asset, store_asset_typed, store_data, store_data_safe,
+    store_data_typed, store_numeric, transfer_if, try_load_asset_typed, verify,
+    verify_delegation_chain, verify_domain_signed, verify_sealed_amount, with_audit_group,
+    AuditGroupEntry, BalanceEvent, BitSet, CallerPolicy, DataSet, DelegationLink, Lease, Pausable,
+    Roles, SpendingLimit,
 };
 
 // Testing helpers on not wasm environments.
@@ -53,3 +70,42 @@ pub const VERSION_MAJOR: &str = env!("CARGO_PKG_VERSION_MAJOR");
 pub const VERSION_MINOR: &str = env!("CARGO_PKG_VERSION_MINOR");
 pub const VERSION_PATCH: &str = env!("CARGO_PKG_VERSION_PATCH");
 pub const VERSION_PRE: &str = env!("CARGO_PKG_VERSION_PRE");
+
+/// SDK version as a `Value` map with `major`, `minor`, `patch` and `pre` fields.
+///
+/// Useful for contracts that want to report the SDK version they were built with.
+pub fn sdk_version_value() -> Value {
+    let major = VERSION_MAJOR.parse::<u64>().unwrap_or_default();
+    let minor = VERSION_MINOR.parse::<u64>().unwrap_or_default();
+    let patch = VERSION_PATCH.parse::<u64>().unwrap_or_default();
+    value!({
+        "major": major,
+        "minor": minor,
+        "patch": patch,
+        "pre": VERSION_PRE,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sdk_version_value_fields() {
+        let version = sdk_version_value();
+
+        assert_eq!(
+            get_value_as_u64!(version, "major").unwrap().to_string(),
+            VERSION_MAJOR
+        );
+        assert_eq!(
+            get_value_as_u64!(version, "minor").unwrap().to_string(),
+            VERSION_MINOR
+        );
+        assert_eq!(
+            get_value_as_u64!(version, "patch").unwrap().to_string(),
+            VERSION_PATCH
+        );
+        assert_eq!(get_value_as_str!(version, "pre").unwrap(), VERSION_PRE);
+    }
+}
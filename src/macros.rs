@@ -26,14 +26,32 @@
 /// Helper macro to construct the application entry point.
 ///
 /// The `input` and `output` values are encoded using MessagePack format.
+///
+/// Optionally, the max nesting depth of the input can be set with
+/// `app_export!(max_depth = 16; method1, method2)`. Deeper inputs are
+/// rejected with `malformed input` before dispatching the method.
+///
+/// The `abi` function describing the methods, see `abi!`, is generated as
+/// well with `app_export!(abi; method1, method2)`.
 #[macro_export]
 macro_rules! app_export {
-    ($($fun:expr),*) => {
+    (max_depth = $max_depth:expr; $($fun:expr),*) => {
+        $crate::app_export!(@export Some($max_depth); $($fun),*);
+    };
+    (abi; $($fun:expr),*) => {
+        $crate::app_export!(@export None; $($fun),*);
+        $crate::abi!($($fun),*);
+    };
+    (@export $max_depth:expr; $($fun:expr),*) => {
         #[doc(hidden)]
         #[no_mangle]
         /// Entry point of the smart contract calls
         fn app_run(ctx: $crate::AppContext, buf: &[u8]) -> Result<Vec<u8>, $crate::WasmError> {
             use $crate::{Serializable, Deserializable};
+            let max_depth: Option<usize> = $max_depth;
+            if let Some(max_depth) = max_depth {
+                $crate::check_args_depth(buf, max_depth)?;
+            }
             match ctx.method {
                 $(
                     stringify!($fun) => {
@@ -62,6 +80,86 @@ macro_rules! app_export {
                 return 0;
         }
     };
+    ($($fun:expr),*) => {
+        $crate::app_export!(@export None; $($fun),*);
+    };
+}
+
+/// Generate the `exported_methods` function returning the names of the given methods.
+///
+/// Invoked by `meta_method!` when the methods are given.
+#[macro_export]
+macro_rules! exported_methods {
+    ($($fun:expr),*) => {
+        /// Names of the methods exported by the smart contract.
+        pub fn exported_methods() -> Vec<String> {
+            vec![$(stringify!($fun).to_string()),*]
+        }
+    };
+}
+
+/// Generate the `abi` function describing the given methods.
+///
+/// Methods documentation is currently left empty. Invoked by `app_export!(abi; ...)`,
+/// off-chain tools can call it in a not wasm build to generate client stubs.
+/// The function is not generated in wasm builds.
+#[macro_export]
+macro_rules! abi {
+    ($($fun:expr),*) => {
+        /// Description of the methods exported by the smart contract.
+        #[cfg(not(target_arch = "wasm32"))]
+        pub fn abi() -> Vec<$crate::DispatchEntry> {
+            vec![$($crate::DispatchEntry {
+                method: stringify!($fun).to_string(),
+                doc: String::new(),
+            }),*]
+        }
+    };
+}
+
+/// Generate the `__meta` method returning the `ContractMeta` of the contract.
+///
+/// The methods list is taken from `exported_methods`, generated as well when
+/// the methods are given, e.g. `meta_method!("name", "1.0"; transfer, __meta)`.
+/// Otherwise `exported_methods!` shall be invoked in the same module.
+/// The `__meta` method shall be exported, e.g. `app_export!(transfer, __meta)`.
+#[macro_export]
+macro_rules! meta_method {
+    ($name:expr, $version:expr; $($fun:expr),*) => {
+        $crate::exported_methods!($($fun),*);
+        $crate::meta_method!($name, $version);
+    };
+    ($name:expr, $version:expr) => {
+        /// Contract metadata method.
+        fn __meta(
+            _ctx: $crate::AppContext,
+            _args: $crate::PackedValue,
+        ) -> $crate::WasmResult<$crate::PackedValue> {
+            let meta = $crate::ContractMeta {
+                name: $name.to_string(),
+                version: $version.to_string(),
+                methods: exported_methods(),
+            };
+            $crate::rmp_serialize_named(&meta).map($crate::PackedValue)
+        }
+    };
+}
+
+/// Generate the `__state` method returning the account data, see `state_dump`.
+///
+/// The method shall be exported, e.g. `app_export!(transfer, __state)`.
+#[macro_export]
+macro_rules! state_method {
+    () => {
+        /// Contract state dump method.
+        fn __state(
+            _ctx: $crate::AppContext,
+            _args: $crate::PackedValue,
+        ) -> $crate::WasmResult<$crate::PackedValue> {
+            let state = $crate::state_dump()?;
+            $crate::rmp_serialize(&state).map($crate::PackedValue)
+        }
+    };
 }
 
 /// Store account data in message pack format.
@@ -134,6 +232,38 @@ macro_rules! get_value_as_u64 {
     };
 }
 
+/// Get an `i64` reference from a `json_serde::Value` by key.
+#[macro_export]
+macro_rules! get_value_as_i64 {
+    ($value:expr, $index:expr) => {
+        $crate::get_value_as!($value, $index, as_i64)
+    };
+}
+
+/// Get a `bool` reference from a `json_serde::Value` by key.
+#[macro_export]
+macro_rules! get_value_as_bool {
+    ($value:expr, $index:expr) => {
+        $crate::get_value_as!($value, $index, as_bool)
+    };
+}
+
+/// Get an `f64` reference from a `json_serde::Value` by key.
+#[macro_export]
+macro_rules! get_value_as_f64 {
+    ($value:expr, $index:expr) => {
+        $crate::get_value_as!($value, $index, as_f64)
+    };
+}
+
+/// Get a bytes reference from a `json_serde::Value` by key.
+#[macro_export]
+macro_rules! get_value_as_bytes {
+    ($value:expr, $index:expr) => {
+        $crate::get_value_as!($value, $index, as_bytes)
+    };
+}
+
 /// Get an `array` reference from a `json_serde::Value` by key.
 #[macro_export]
 macro_rules! get_value_as_array {
@@ -142,6 +272,140 @@ macro_rules! get_value_as_array {
     };
 }
 
+/// Get a `Vec<String>` from a `json_serde::Value` array by key.
+#[macro_export]
+macro_rules! get_value_as_str_vec {
+    ($value:expr, $index:expr) => {
+        $value
+            .get(&value!($index))
+            .ok_or_else(|| {
+                $crate::WasmError::new(&format!("`{}::{}` not found", stringify!($value), $index))
+            })
+            .and_then(|value| {
+                $crate::value::as_str_vec(value).map_err(|_err| {
+                    $crate::WasmError::new(&format!(
+                        "`{}::{}` has bad type",
+                        stringify!($value),
+                        $index
+                    ))
+                })
+            })
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! positional_arg {
+    (@type str) => {
+        &str
+    };
+    (@type u64) => {
+        u64
+    };
+    (@type value) => {
+        &$crate::Value
+    };
+    (@as $item:expr, str) => {
+        $item.as_str()
+    };
+    (@as $item:expr, u64) => {
+        $item.as_u64()
+    };
+    (@as $item:expr, value) => {
+        Some($item)
+    };
+}
+
+/// Bind a tuple from a `json_serde::Value` array of positional arguments.
+///
+/// The array length shall match the number of requested types, each element
+/// is converted according to its type: `str`, `u64` or `value` (as-is).
+///
+/// ```ignore
+/// let (from, to, units) = positional_args!(args, str, str, u64)?;
+/// ```
+#[macro_export]
+macro_rules! positional_args {
+    ($value:expr, $($vtype:ident),+) => {{
+        fn positional_args(
+            items: &[$crate::Value],
+        ) -> $crate::WasmResult<($($crate::positional_arg!(@type $vtype),)+)> {
+            let mut items = items.iter().enumerate();
+            Ok(($({
+                let (idx, item) = items
+                    .next()
+                    .ok_or_else(|| $crate::WasmError::new("missing argument"))?;
+                $crate::positional_arg!(@as item, $vtype).ok_or_else(|| {
+                    $crate::WasmError::new(&format!(
+                        "`{}::{}` has bad type",
+                        stringify!($value),
+                        idx
+                    ))
+                })?
+            },)+))
+        }
+        match &$value {
+            $crate::Value::Seq(items) if items.len() == [$(stringify!($vtype)),+].len() => {
+                positional_args(items)
+            }
+            $crate::Value::Seq(_) => Err($crate::WasmError::new(&format!(
+                "`{}` has bad arity",
+                stringify!($value)
+            ))),
+            _ => Err($crate::WasmError::new(&format!(
+                "`{}` is not an array",
+                stringify!($value)
+            ))),
+        }
+    }};
+}
+
+/// Deserialize and validate the method arguments, returning early on failure.
+///
+/// Methods should deserialize and validate all their arguments before any
+/// side effect (e.g. `store_data`) to never leave a partial state when the
+/// input is malformed. The optional validator receives a reference to the
+/// deserialized arguments and returns a `WasmResult<()>`; its parameter type
+/// shall be annotated.
+///
+/// ```ignore
+/// let args: TransferArgs = validate_args!(args, |args: &TransferArgs| check(args));
+/// ```
+#[macro_export]
+macro_rules! validate_args {
+    ($args:expr) => {
+        $crate::rmp_deserialize(&$args)?
+    };
+    ($args:expr, $validate:expr) => {{
+        let args = $crate::rmp_deserialize(&$args)?;
+        ($validate)(&args)?;
+        args
+    }};
+}
+
+/// Return early with the given error message when the value is not within the range.
+///
+/// Any range form is accepted, e.g. `require_range!(bps, 0..=10000, "bps out of range")`
+/// or `require_range!(units, 1.., "invalid units")`.
+#[macro_export]
+macro_rules! require_range {
+    ($value:expr, $range:expr, $msg:expr) => {
+        if !($range).contains(&$value) {
+            return Err($crate::WasmError::new($msg));
+        }
+    };
+}
+
+/// Return early with `contract is paused` when the contract is paused.
+///
+/// See `Pausable`.
+#[macro_export]
+macro_rules! when_not_paused {
+    () => {
+        $crate::Pausable::require_not_paused()?;
+    };
+}
+
 /// Helper macro around sdk logging facility to allow format strings.
 #[macro_export]
 macro_rules! log {
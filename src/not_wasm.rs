@@ -20,13 +20,20 @@
 
 use crate::{
     common::*,
-    core::{AppOutput, PublicKey},
+    core::{AppInput, AppOutput, PublicKey},
+    hash::{Hash, HashAlgorithm},
     host_wrap::{load_asset_typed, store_asset_typed},
-    tai::{Asset, AssetLockArgs, AssetTransferArgs, LockPrivilege, LockType},
+    tai::{Asset, AssetLockArgs, AssetMintArgs, AssetTransferArgs, LockPrivilege, LockType},
 };
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use serde_value::Value;
 use sha2::{Digest, Sha256};
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, VecDeque},
+    rc::Rc,
+};
 
 const MEMORY_SIZE: usize = 16384;
 
@@ -45,11 +52,34 @@ struct Account {
     contract: Vec<u8>,
 }
 
+/// Record of a contract call performed via `hf_call`/`hf_s_call`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallRecord {
+    pub depth: u16,
+    pub account: String,
+    pub method: String,
+    pub args: Vec<u8>,
+}
+
 struct ThreadData {
     memory: Memory,
     app_ctx: usize,
     accounts: HashMap<String, Account>,
     contract_methods: HashMap<String, ContractFunc>,
+    call_trace: Vec<CallRecord>,
+    call_stack: Vec<(String, String)>,
+    max_call_depth: u16,
+    cycle_detection: bool,
+    events: Vec<(String, Vec<u8>)>,
+    drand_value: Option<u64>,
+    drand_sequence: VecDeque<u64>,
+    verify_result: Option<bool>,
+    local_verify: bool,
+    block_time: Option<u64>,
+    memo_size_max: Option<usize>,
+    network_params: HashMap<String, NetworkParams>,
+    asset_supply: HashMap<String, u64>,
+    asset_max_supply: HashMap<String, u64>,
 }
 
 impl Default for ThreadData {
@@ -62,6 +92,20 @@ impl Default for ThreadData {
             app_ctx: 0,
             accounts: HashMap::new(),
             contract_methods: HashMap::new(),
+            call_trace: Vec::new(),
+            call_stack: Vec::new(),
+            max_call_depth: 0,
+            cycle_detection: false,
+            events: Vec::new(),
+            drand_value: None,
+            drand_sequence: VecDeque::new(),
+            verify_result: None,
+            local_verify: false,
+            block_time: None,
+            memo_size_max: None,
+            network_params: HashMap::new(),
+            asset_supply: HashMap::new(),
+            asset_max_supply: HashMap::new(),
         }
     }
 }
@@ -85,6 +129,25 @@ pub fn create_app_context<'a>(owner: &'a str, caller: &'a str) -> AppContext<'a>
     }
 }
 
+/// Create an application context setting every field.
+pub fn create_app_context_full<'a>(
+    owner: &'a str,
+    caller: &'a str,
+    origin: &'a str,
+    method: &'a str,
+    network: &'a str,
+    depth: u16,
+) -> AppContext<'a> {
+    AppContext {
+        owner,
+        caller,
+        method,
+        depth,
+        network,
+        origin,
+    }
+}
+
 pub fn get_app_ctx<'a>() -> &'a AppContext<'a> {
     let dat = thread_data();
     let addr = dat.borrow().app_ctx;
@@ -183,6 +246,35 @@ pub fn set_account_asset_gen<T: Serialize>(dst_id: &str, asset: &str, value: T)
     set_account_asset(dst_id, asset, &buf);
 }
 
+/// Seed an `Asset` holding `units` and locked with the given privilege and type.
+pub fn seed_locked_asset(
+    account_id: &str,
+    asset_id: &str,
+    units: u64,
+    privilege: LockPrivilege,
+    lock_type: LockType,
+) {
+    let asset = Asset {
+        units,
+        lock: Some((privilege, lock_type)),
+    };
+    set_account_asset_gen(account_id, asset_id, asset);
+}
+
+/// Assert that the account `Asset` lock is the expected one.
+pub fn assert_asset_lock(
+    account_id: &str,
+    asset_id: &str,
+    expected: Option<(LockPrivilege, LockType)>,
+) {
+    let asset: Asset = get_account_asset_gen(account_id, asset_id);
+    assert_eq!(
+        asset.lock, expected,
+        "unexpected lock for asset `{}` in account `{}`",
+        asset_id, account_id
+    );
+}
+
 /// Register a contract method to an account.
 pub fn set_contract_method(account_id: &str, method: &str, func: ContractFunc) {
     let dat = thread_data();
@@ -191,6 +283,53 @@ pub fn set_contract_method(account_id: &str, method: &str, func: ContractFunc) {
     methods.insert(key, func);
 }
 
+/// Outcome of `smoke_test_account`, the failed methods come with the error message.
+#[derive(Debug, Default, PartialEq)]
+pub struct SmokeReport {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+/// Invoke every method registered to an account with the given args.
+///
+/// Methods are invoked in alphabetical order as called by the account itself.
+/// Panicking methods are reported as failed.
+pub fn smoke_test_account(account_id: &str, default_args: &Value) -> SmokeReport {
+    let prefix = format!("{}:", account_id);
+    let mut methods: Vec<(String, ContractFunc)> = thread_data()
+        .borrow()
+        .contract_methods
+        .iter()
+        .filter_map(|(key, func)| {
+            key.strip_prefix(&prefix)
+                .map(|method| (method.to_owned(), *func))
+        })
+        .collect();
+    methods.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let args = rmp_serialize(default_args).unwrap();
+    let prev_ctx = thread_data().borrow().app_ctx;
+    let mut report = SmokeReport::default();
+    for (method, func) in methods {
+        let create_ctx = || AppContext {
+            method: &method,
+            ..create_app_context(account_id, account_id)
+        };
+        let ctx = create_ctx();
+        set_app_ctx(&ctx);
+        let input = PackedValue(args.clone());
+        let res =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| func(create_ctx(), input)));
+        match res {
+            Ok(Ok(_)) => report.succeeded.push(method.clone()),
+            Ok(Err(err)) => report.failed.push((method.clone(), err.to_string())),
+            Err(_) => report.failed.push((method.clone(), "panicked".to_owned())),
+        }
+    }
+    thread_data().borrow_mut().app_ctx = prev_ctx;
+    report
+}
+
 /// Register a contract hash to an account.
 pub fn set_contract_hash(account_id: &str, contract: &[u8]) {
     let dat = thread_data();
@@ -199,6 +338,334 @@ pub fn set_contract_hash(account_id: &str, contract: &[u8]) {
     account.contract = contract.to_vec();
 }
 
+// Serializable account snapshot, ordered to get a deterministic encoding.
+#[derive(Serialize, Deserialize)]
+struct AccountSnapshot {
+    assets: BTreeMap<String, ByteBuf>,
+    data: BTreeMap<String, ByteBuf>,
+    contract: ByteBuf,
+}
+
+fn to_byte_bufs(map: &HashMap<String, Vec<u8>>) -> BTreeMap<String, ByteBuf> {
+    map.iter()
+        .map(|(key, buf)| (key.clone(), ByteBuf::from(buf.clone())))
+        .collect()
+}
+
+fn from_byte_bufs(map: BTreeMap<String, ByteBuf>) -> HashMap<String, Vec<u8>> {
+    map.into_iter()
+        .map(|(key, buf)| (key, buf.into_vec()))
+        .collect()
+}
+
+/// Serialize the accounts state (assets, data and contract) of every account.
+/// The registered contract methods are not part of the state.
+pub fn serialize_state() -> Vec<u8> {
+    let dat = thread_data();
+    let accounts = &dat.borrow().accounts;
+    let snapshot: BTreeMap<&String, AccountSnapshot> = accounts
+        .iter()
+        .map(|(id, account)| {
+            let account = AccountSnapshot {
+                assets: to_byte_bufs(&account.assets),
+                data: to_byte_bufs(&account.data),
+                contract: ByteBuf::from(account.contract.clone()),
+            };
+            (id, account)
+        })
+        .collect();
+    rmp_serialize(&snapshot).unwrap()
+}
+
+/// Replace the accounts state with the one produced by `serialize_state`.
+pub fn deserialize_state(buf: &[u8]) -> WasmResult<()> {
+    let snapshot: BTreeMap<String, AccountSnapshot> = rmp_deserialize(buf)?;
+    let accounts = snapshot
+        .into_iter()
+        .map(|(id, account)| {
+            let account = Account {
+                assets: from_byte_bufs(account.assets),
+                data: from_byte_bufs(account.data),
+                contract: account.contract.into_vec(),
+            };
+            (id, account)
+        })
+        .collect();
+    thread_data().borrow_mut().accounts = accounts;
+    Ok(())
+}
+
+/// Deterministic hash of the account state: its data, assets and contract.
+///
+/// Entries are hashed sorted by key, each field is prefixed by its length.
+pub fn account_state_root(account_id: &str) -> Hash {
+    fn push_field(buf: &mut Vec<u8>, field: &[u8]) {
+        buf.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        buf.extend_from_slice(field);
+    }
+
+    fn push_entries(buf: &mut Vec<u8>, map: &HashMap<String, Vec<u8>>) {
+        let entries: BTreeMap<&String, &Vec<u8>> = map.iter().collect();
+        buf.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        entries.into_iter().for_each(|(key, value)| {
+            push_field(buf, key.as_bytes());
+            push_field(buf, value);
+        });
+    }
+
+    let dat = thread_data();
+    let accounts = &dat.borrow().accounts;
+    let empty = Account::default();
+    let account = accounts.get(account_id).unwrap_or(&empty);
+    let mut buf = Vec::new();
+    push_entries(&mut buf, &account.data);
+    push_entries(&mut buf, &account.assets);
+    push_field(&mut buf, &account.contract);
+    Hash::from_data(HashAlgorithm::Sha256, &buf)
+}
+
+/// Get the ids of the accounts present in the mocked state, sorted.
+pub fn list_accounts() -> Vec<String> {
+    let dat = thread_data();
+    let accounts = &dat.borrow().accounts;
+    let mut ids: Vec<String> = accounts.keys().cloned().collect();
+    ids.sort();
+    ids
+}
+
+/// Storage used by the account, the sum of its data and assets values lengths.
+pub fn account_storage_size(account_id: &str) -> usize {
+    let dat = thread_data();
+    let accounts = &dat.borrow().accounts;
+    accounts
+        .get(account_id)
+        .map(|account| {
+            account
+                .data
+                .values()
+                .chain(account.assets.values())
+                .map(|value| value.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Get the calls recorded so far.
+pub fn get_call_trace() -> Vec<CallRecord> {
+    thread_data().borrow().call_trace.clone()
+}
+
+/// Clear the recorded calls.
+pub fn clear_call_trace() {
+    thread_data().borrow_mut().call_trace.clear();
+}
+
+/// Get the max call depth reached by the calls performed so far.
+pub fn get_max_call_depth_reached() -> u16 {
+    thread_data().borrow().max_call_depth
+}
+
+/// Reset the max call depth reached.
+pub fn reset_max_call_depth_reached() {
+    thread_data().borrow_mut().max_call_depth = 0;
+}
+
+/// Enable or disable the detection of call cycles (e.g. A calls B calls A).
+///
+/// When enabled, calling an `(account, method)` pair already on the call
+/// stack fails with `call cycle detected`.
+pub fn set_cycle_detection(enabled: bool) {
+    thread_data().borrow_mut().cycle_detection = enabled;
+}
+
+/// Force the value returned by `hf_drand`, clamped to the requested range.
+pub fn set_drand_value(value: u64) {
+    thread_data().borrow_mut().drand_value = Some(value);
+}
+
+/// Feed the given values, in order, to the successive `hf_drand` calls.
+///
+/// Each value is clamped to the requested range. Once the values are exhausted
+/// `hf_drand` falls back to the forced value, if any, or to `max / 2`.
+pub fn set_drand_sequence(values: Vec<u64>) {
+    thread_data().borrow_mut().drand_sequence = values.into();
+}
+
+/// Run `f` feeding the given values, in order, to the successive `hf_drand` calls.
+///
+/// Each value is clamped to the requested range. Once the values are exhausted
+/// `hf_drand` falls back to the forced value, if any, or to `max / 2`.
+/// When `f` returns, any sequence previously set is restored.
+pub fn with_drand_sequence<F, T>(values: &[u64], f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let prev = std::mem::replace(
+        &mut thread_data().borrow_mut().drand_sequence,
+        values.iter().copied().collect(),
+    );
+    let res = f();
+    thread_data().borrow_mut().drand_sequence = prev;
+    res
+}
+
+/// Set the max size of the transfer `data` accepted by the mocked assets.
+/// By default the size is not limited.
+pub fn set_memo_size_max(max: usize) {
+    thread_data().borrow_mut().memo_size_max = Some(max);
+}
+
+// Check the transfer `data` against the configured max size.
+fn check_memo_size(args: &AssetTransferArgs) -> WasmResult<()> {
+    let max = thread_data().borrow().memo_size_max;
+    match (max, &args.data) {
+        (Some(max), Some(data)) => require_memo_size(data, max),
+        _ => Ok(()),
+    }
+}
+
+/// Register the parameters returned by `network_params` for the given network.
+pub fn set_network_params(network: &str, params: NetworkParams) {
+    let dat = thread_data();
+    let network_params = &mut dat.borrow_mut().network_params;
+    network_params.insert(network.to_owned(), params);
+}
+
+/// Get the parameters registered for the given network, if any.
+pub fn get_network_params(network: &str) -> Option<NetworkParams> {
+    thread_data().borrow().network_params.get(network).cloned()
+}
+
+/// Enable or disable the real signatures verification in `hf_verify`.
+///
+/// When enabled, and the result is not forced via `set_verify_result`, the
+/// signatures are checked using `verify_local`.
+pub fn set_local_verify(enabled: bool) {
+    thread_data().borrow_mut().local_verify = enabled;
+}
+
+/// Force the result returned by `hf_verify`.
+pub fn set_verify_result(ok: bool) {
+    thread_data().borrow_mut().verify_result = Some(ok);
+}
+
+/// Force the timestamp returned by `hf_get_block_time`.
+pub fn set_block_time(timestamp: u64) {
+    thread_data().borrow_mut().block_time = Some(timestamp);
+}
+
+/// Builder to configure the mocked host behavior in one call.
+///
+/// Behaviors that are not configured get their default mocked implementation.
+#[derive(Default)]
+pub struct MockHost {
+    drand: Option<u64>,
+    verify: Option<bool>,
+    timestamp: Option<u64>,
+}
+
+impl MockHost {
+    /// Constructor.
+    pub fn new() -> Self {
+        MockHost::default()
+    }
+
+    /// Value returned by `drand`.
+    pub fn drand(mut self, value: u64) -> Self {
+        self.drand = Some(value);
+        self
+    }
+
+    /// Result returned by `verify`.
+    pub fn verify(mut self, ok: bool) -> Self {
+        self.verify = Some(ok);
+        self
+    }
+
+    /// Timestamp returned by `get_block_time`.
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Apply the configuration to the current thread mocked host.
+    pub fn build(self) {
+        let dat = thread_data();
+        let mut dat = dat.borrow_mut();
+        dat.drand_value = self.drand;
+        dat.verify_result = self.verify;
+        dat.block_time = self.timestamp;
+    }
+}
+
+/// Get the events emitted so far as `(event_name, event_data)` pairs.
+/// Events are kept in emission order, thus a batch is always contiguous.
+pub fn get_emitted_events() -> Vec<(String, Vec<u8>)> {
+    thread_data().borrow().events.clone()
+}
+
+/// Clear the events emitted so far.
+pub fn clear_emitted_events() {
+    thread_data().borrow_mut().events.clear();
+}
+
+/// Assert that the emitted events are exactly the expected `(event_name, event_data)` list.
+///
+/// The events data is deserialized to a `Value`, falling back to `Value::Bytes`
+/// if not valid MessagePack. Values are compared by their MessagePack encoding,
+/// thus integers match regardless of their width. On mismatch the first
+/// differing index is reported.
+pub fn assert_events(expected: &[(&str, Value)]) {
+    let encode = |value: &Value| rmp_serialize(value).unwrap_or_default();
+    let actual: Vec<(String, Value)> = get_emitted_events()
+        .into_iter()
+        .map(|(name, data)| {
+            let value = rmp_deserialize(&data).unwrap_or(Value::Bytes(data));
+            (name, value)
+        })
+        .collect();
+    let matches = |(name, value): &(String, Value), (exp_name, exp_value): &(&str, Value)| {
+        name == exp_name && encode(value) == encode(exp_value)
+    };
+    let index = actual
+        .iter()
+        .zip(expected)
+        .position(|(actual, expected)| !matches(actual, expected))
+        .or_else(|| match actual.len() == expected.len() {
+            true => None,
+            false => Some(actual.len().min(expected.len())),
+        });
+    if let Some(index) = index {
+        panic!(
+            "emitted events mismatch at index {}\n  actual: {:?}\nexpected: {:?}",
+            index,
+            actual.get(index),
+            expected.get(index)
+        );
+    }
+}
+
+/// Max number of args bytes shown by `format_call_trace`.
+const TRACE_ARGS_MAX: usize = 16;
+
+/// Render the recorded calls as a tree, indenting each call by its depth.
+pub fn format_call_trace() -> String {
+    get_call_trace()
+        .iter()
+        .map(|record| {
+            let indent = "  ".repeat(record.depth.saturating_sub(1) as usize);
+            let mut args = hex::encode(&record.args[..record.args.len().min(TRACE_ARGS_MAX)]);
+            if record.args.len() > TRACE_ARGS_MAX {
+                args.push_str("..");
+            }
+            format!(
+                "{}{}::{}({})\n",
+                indent, record.account, record.method, args
+            )
+        })
+        .collect()
+}
+
 pub fn memory_base() -> usize {
     thread_data().borrow().memory.buf.as_ptr() as usize
 }
@@ -218,6 +685,29 @@ pub fn write_mem(buf: &[u8]) -> i32 {
     prev_off as i32
 }
 
+/// Execute the contract entry point with raw input and arguments buffers.
+///
+/// The buffers are written to the mocked memory and the `AppOutput` returned
+/// by `run` is decoded, on failure its message is returned as error.
+pub fn execute(input: &AppInput, args: &[u8]) -> WasmResult<Vec<u8>> {
+    let input_buf = rmp_serialize(input)?;
+    let input_addr = write_mem(&input_buf);
+    let args_addr = write_mem(args);
+
+    let wslice = crate::export::run(
+        input_addr,
+        input_buf.len() as i32,
+        args_addr,
+        args.len() as i32,
+    );
+
+    let output: AppOutput = rmp_deserialize(slice_from_wslice(wslice))?;
+    match output.success {
+        true => Ok(output.data.to_vec()),
+        false => Err(WasmError::from_app_output(&output)),
+    }
+}
+
 pub fn call_wrap<F, T, U>(func: F, ctx: AppContext, args: T) -> WasmResult<U>
 where
     F: FnOnce(AppContext, T) -> WasmResult<U>,
@@ -234,20 +724,27 @@ pub extern "C" fn hf_log(str_addr: i32, str_size: i32) {
 
 #[no_mangle]
 pub extern "C" fn hf_emit(id_addr: i32, id_size: i32, data_addr: i32, data_size: i32) {
-    let id = slice_from_mem(id_addr, id_size);
+    let buf = slice_from_mem(id_addr, id_size);
+    let id = match str_from_bytes(buf) {
+        Ok(id) => id,
+        Err(_) => return,
+    };
     let data = slice_from_mem(data_addr, data_size);
-    println!(
-        "[EMIT] - id: {}, data: {}",
-        String::from_utf8_lossy(id),
-        hex::encode(data)
-    );
+    println!("[EMIT] - id: {}, data: {}", id, hex::encode(data));
+    thread_data()
+        .borrow_mut()
+        .events
+        .push((id.to_string(), data.to_vec()));
 }
 
 #[no_mangle]
 pub extern "C" fn hf_get_keys(pattern_addr: i32, pattern_size: i32) -> WasmSlice {
     let ctx: &AppContext = get_app_ctx();
     let buf = slice_from_mem(pattern_addr, pattern_size);
-    let pattern = unsafe { std::str::from_utf8_unchecked(buf) };
+    let pattern = match str_from_bytes(buf) {
+        Ok(pattern) => pattern,
+        Err(err) => return AppOutput::ko(&err.to_string()).into(),
+    };
 
     let data_buf;
 
@@ -281,7 +778,10 @@ pub extern "C" fn hf_get_keys(pattern_addr: i32, pattern_size: i32) -> WasmSlice
 pub extern "C" fn hf_store_data(key_addr: i32, key_size: i32, data_addr: i32, data_size: i32) {
     let ctx: &AppContext = get_app_ctx();
     let buf = slice_from_mem(key_addr, key_size);
-    let key = unsafe { std::str::from_utf8_unchecked(buf) };
+    let key = match str_from_bytes(buf) {
+        Ok(key) => key,
+        Err(_) => return,
+    };
     let data = slice_from_mem(data_addr, data_size);
     set_account_data(ctx.owner, key, data);
 }
@@ -290,7 +790,10 @@ pub extern "C" fn hf_store_data(key_addr: i32, key_size: i32, data_addr: i32, da
 pub extern "C" fn hf_load_data(key_addr: i32, key_size: i32) -> WasmSlice {
     let ctx: &AppContext = get_app_ctx();
     let buf = slice_from_mem(key_addr, key_size);
-    let key = unsafe { std::str::from_utf8_unchecked(buf) };
+    let key = match str_from_bytes(buf) {
+        Ok(key) => key,
+        Err(_) => return slice_to_wslice(&[]),
+    };
     let buf = get_account_data(ctx.owner, key);
     slice_to_wslice(&buf)
 }
@@ -298,7 +801,10 @@ pub extern "C" fn hf_load_data(key_addr: i32, key_size: i32) -> WasmSlice {
 #[no_mangle]
 pub extern "C" fn hf_get_account_contract(id_addr: i32, id_size: i32) -> WasmSlice {
     let buf = slice_from_mem(id_addr, id_size);
-    let account_id = unsafe { std::str::from_utf8_unchecked(buf) };
+    let account_id = match str_from_bytes(buf) {
+        Ok(account_id) => account_id,
+        Err(_) => return slice_to_wslice(&[]),
+    };
     let buf = get_account_contract(account_id);
     slice_to_wslice(&buf)
 }
@@ -311,9 +817,15 @@ pub extern "C" fn hf_is_callable(
     method_size: i32,
 ) -> i32 {
     let buf = slice_from_mem(id_addr, id_size);
-    let account_id = unsafe { std::str::from_utf8_unchecked(buf) };
+    let account_id = match str_from_bytes(buf) {
+        Ok(account_id) => account_id,
+        Err(_) => return 0,
+    };
     let buf = slice_from_mem(method_addr, method_size);
-    let method = unsafe { std::str::from_utf8_unchecked(buf) };
+    let method = match str_from_bytes(buf) {
+        Ok(method) => method,
+        Err(_) => return 0,
+    };
     is_callable(account_id, method)
 }
 
@@ -321,7 +833,10 @@ pub extern "C" fn hf_is_callable(
 pub extern "C" fn hf_remove_data(key_addr: i32, key_size: i32) {
     let ctx: &AppContext = get_app_ctx();
     let buf = slice_from_mem(key_addr, key_size);
-    let key = unsafe { std::str::from_utf8_unchecked(buf) };
+    let key = match str_from_bytes(buf) {
+        Ok(key) => key,
+        Err(_) => return,
+    };
     set_account_data(ctx.owner, key, &[]);
 }
 
@@ -329,7 +844,10 @@ pub extern "C" fn hf_remove_data(key_addr: i32, key_size: i32) {
 pub extern "C" fn hf_load_asset(src_id_addr: i32, src_id_size: i32) -> WasmSlice {
     let ctx: &AppContext = get_app_ctx();
     let buf = slice_from_mem(src_id_addr, src_id_size);
-    let src_id = unsafe { std::str::from_utf8_unchecked(buf) };
+    let src_id = match str_from_bytes(buf) {
+        Ok(src_id) => src_id,
+        Err(_) => return slice_to_wslice(&[]),
+    };
     let buf = get_account_asset(src_id, ctx.owner);
     slice_to_wslice(&buf)
 }
@@ -343,7 +861,10 @@ pub extern "C" fn hf_store_asset(
 ) {
     let ctx: &AppContext = get_app_ctx();
     let buf = slice_from_mem(dst_id_addr, dst_id_size);
-    let dst_id = unsafe { std::str::from_utf8_unchecked(buf) };
+    let dst_id = match str_from_bytes(buf) {
+        Ok(dst_id) => dst_id,
+        Err(_) => return,
+    };
     let value = slice_from_mem(value_addr, value_size);
     set_account_asset(dst_id, ctx.owner, value);
 }
@@ -352,7 +873,10 @@ pub extern "C" fn hf_store_asset(
 pub extern "C" fn hf_remove_asset(dst_id_addr: i32, dst_id_size: i32) {
     let ctx: &AppContext = get_app_ctx();
     let buf = slice_from_mem(dst_id_addr, dst_id_size);
-    let dst_id = unsafe { std::str::from_utf8_unchecked(buf) };
+    let dst_id = match str_from_bytes(buf) {
+        Ok(dst_id) => dst_id,
+        Err(_) => return,
+    };
     remove_account_asset(dst_id, ctx.owner);
 }
 
@@ -369,15 +893,21 @@ pub extern "C" fn hf_sha256(data_addr: i32, data_size: i32) -> WasmSlice {
 
 #[no_mangle]
 pub extern "C" fn hf_drand(max: u64) -> u64 {
-    max / 2
+    let dat = thread_data();
+    let mut dat = dat.borrow_mut();
+    match dat.drand_sequence.pop_front().or(dat.drand_value) {
+        Some(value) => value.min(max.saturating_sub(1)),
+        None => max / 2,
+    }
 }
 
 #[no_mangle]
 pub extern "C" fn hf_get_block_time() -> u64 {
-    1652780598
+    thread_data().borrow().block_time.unwrap_or(1652780598)
 }
 
-// Use the first byte of the sign to return success or error.
+// Unless forced via `set_verify_result` or when local verification is enabled
+// via `set_local_verify`, use the first byte of the sign to return success or error.
 #[no_mangle]
 pub extern "C" fn hf_verify(
     pk_addr: i32,
@@ -388,14 +918,23 @@ pub extern "C" fn hf_verify(
     sign_size: i32,
 ) -> i32 {
     let pk = slice_from_mem(pk_addr, pk_size);
-    let _pk: PublicKey = match rmp_deserialize(pk) {
+    let pk: PublicKey = match rmp_deserialize(pk) {
         Ok(val) => val,
         Err(_) => return 0,
     };
-    let _data = slice_from_mem(data_addr, data_size);
+    let data = slice_from_mem(data_addr, data_size);
     let sign = slice_from_mem(sign_addr, sign_size);
 
-    sign[0] as i32
+    let (verify_result, local_verify) = {
+        let dat = thread_data();
+        let dat = dat.borrow();
+        (dat.verify_result, dat.local_verify)
+    };
+    match verify_result {
+        Some(ok) => ok as i32,
+        None if local_verify => crate::verify_local(&pk, data, sign) as i32,
+        None => sign[0] as i32,
+    }
 }
 
 #[no_mangle]
@@ -434,10 +973,16 @@ pub extern "C" fn hf_s_call(
 ) -> WasmSlice {
     let ctx: &AppContext = get_app_ctx();
     let slice = slice_from_mem(account_addr, account_size);
-    let account = unsafe { std::str::from_utf8_unchecked(slice) };
+    let account = match str_from_bytes(slice) {
+        Ok(account) => account,
+        Err(err) => return AppOutput::ko(&err.to_string()).into(),
+    };
     let contract = slice_from_mem(contract_addr, contract_size).to_owned();
     let slice = slice_from_mem(method_addr, method_size);
-    let method = unsafe { std::str::from_utf8_unchecked(slice) };
+    let method = match str_from_bytes(slice) {
+        Ok(method) => method,
+        Err(err) => return AppOutput::ko(&err.to_string()).into(),
+    };
     let args = slice_from_mem(data_addr, data_size).to_owned();
 
     println!(
@@ -448,6 +993,28 @@ pub extern "C" fn hf_s_call(
         hex::encode(args.clone())
     );
 
+    {
+        let dat = thread_data();
+        let mut dat = dat.borrow_mut();
+        dat.call_trace.push(CallRecord {
+            depth: ctx.depth + 1,
+            account: account.to_owned(),
+            method: method.to_owned(),
+            args: args.clone(),
+        });
+        dat.max_call_depth = dat.max_call_depth.max(ctx.depth + 1);
+    }
+
+    {
+        let dat = thread_data();
+        let mut dat = dat.borrow_mut();
+        let pair = (account.to_owned(), method.to_owned());
+        if dat.cycle_detection && dat.call_stack.contains(&pair) {
+            return AppOutput::ko("call cycle detected").into();
+        }
+        dat.call_stack.push(pair);
+    }
+
     let method_func = {
         let method_name = format!("{}:{}", account, method);
         let dat = thread_data();
@@ -459,6 +1026,7 @@ pub extern "C" fn hf_s_call(
                 None => false, // return AppOutput::ko("incompatible contract app").into(),
             };
             if !val {
+                dat.borrow_mut().call_stack.pop();
                 return AppOutput::ko("incompatible contract app").into();
             }
         }
@@ -469,7 +1037,10 @@ pub extern "C" fn hf_s_call(
         };
         match method_func {
             Some(method) => method.to_owned(),
-            None => return AppOutput::ko("method not found").into(),
+            None => {
+                dat.borrow_mut().call_stack.pop();
+                return AppOutput::ko("method not found").into();
+            }
         }
     };
 
@@ -490,17 +1061,27 @@ pub extern "C" fn hf_s_call(
         Err(err) => AppOutput::ko(&err.to_string()).into(),
     };
     set_app_ctx(prev_ctx);
+    thread_data().borrow_mut().call_stack.pop();
 
     result
 }
 
+// Check if the asset lock blocks the flow direction of the given lock type.
+fn is_flow_locked(asset: &Asset, direction: LockType) -> bool {
+    matches!(
+        asset.lock,
+        Some((_, lock_type)) if lock_type == direction || lock_type == LockType::Full
+    )
+}
+
 /// Mocked TAI Asset `transfer` method used by the tests.
 pub fn asset_transfer(_ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue> {
     let args: AssetTransferArgs = rmp_deserialize(&args).unwrap();
+    check_memo_size(&args)?;
 
     // Withdraw
     let mut value: Asset = load_asset_typed(args.from);
-    if value.lock.is_some() {
+    if is_flow_locked(&value, LockType::Withdraw) {
         return Err(WasmError::new("source account locked"));
     }
     if value.units < args.units {
@@ -511,7 +1092,7 @@ pub fn asset_transfer(_ctx: AppContext, args: PackedValue) -> WasmResult<PackedV
 
     // Deposit
     let mut value: Asset = load_asset_typed(args.to);
-    if value.lock.is_some() {
+    if is_flow_locked(&value, LockType::Deposit) {
         return Err(WasmError::new("destination account locked"));
     }
     value.units += args.units;
@@ -521,6 +1102,41 @@ pub fn asset_transfer(_ctx: AppContext, args: PackedValue) -> WasmResult<PackedV
     Ok(PackedValue(buf))
 }
 
+/// Set the max supply of an asset enforced by the mocked `mint` method.
+pub fn set_asset_max_supply(asset: &str, cap: u64) {
+    let dat = thread_data();
+    let max_supply = &mut dat.borrow_mut().asset_max_supply;
+    max_supply.insert(asset.to_owned(), cap);
+}
+
+/// Mocked TAI Asset `mint` method used by the tests.
+///
+/// Mints are rejected if they exceed the asset max supply, if any.
+pub fn asset_mint(ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue> {
+    let args: AssetMintArgs = rmp_deserialize(&args)?;
+    {
+        let dat = thread_data();
+        let mut dat = dat.borrow_mut();
+        let supply = dat.asset_supply.get(ctx.owner).copied().unwrap_or_default();
+        let supply = supply
+            .checked_add(args.units)
+            .ok_or_else(|| WasmError::new("max supply exceeded"))?;
+        if let Some(cap) = dat.asset_max_supply.get(ctx.owner) {
+            if supply > *cap {
+                return Err(WasmError::new("max supply exceeded"));
+            }
+        }
+        dat.asset_supply.insert(ctx.owner.to_owned(), supply);
+    }
+
+    let mut value: Asset = load_asset_typed(args.to);
+    value.units += args.units;
+    store_asset_typed(args.to, value);
+
+    let buf = rmp_serialize(&()).unwrap();
+    Ok(PackedValue(buf))
+}
+
 /// Mocked TAI Asset `balance` method used by the tests.
 pub fn asset_balance(ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
     let value: Asset = load_asset_typed(ctx.caller);
@@ -531,9 +1147,20 @@ pub fn asset_balance(ctx: AppContext, _args: PackedValue) -> WasmResult<PackedVa
     Ok(PackedValue(buf))
 }
 
+/// Mocked TAI Asset `state` method used by the tests.
+///
+/// The queried account can be passed as argument, defaults to the caller.
+pub fn asset_state(ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue> {
+    let account = rmp_deserialize::<&str>(&args).unwrap_or(ctx.caller);
+    let value: Asset = load_asset_typed(account);
+    let buf = rmp_serialize(&value).unwrap();
+    Ok(PackedValue(buf))
+}
+
 /// Mocked Advanced Asset `transfer` method used by the tests.
 pub fn adv_asset_transfer(_ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue> {
     let args: AssetTransferArgs = rmp_deserialize(&args).unwrap();
+    check_memo_size(&args)?;
 
     // Withdraw
     let mut value_units: u64 = load_asset_typed(args.from);
@@ -572,3 +1199,701 @@ pub fn asset_lock(ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue>
     let buf = rmp_serialize(&prev_lock).unwrap();
     Ok(PackedValue(buf))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "QmYHnEQLdf5h7KYbjFPuHSRk2SPgdXrJWFh5W696HPfq7i";
+    const CALLER: &str = "QmTeNPcQnoxinb9bcQhuFxteTQ4sN3qSWJNoHjgEr84zNY";
+    const OUTER: &str = "QmZKrfoq8ZtkH445373qFQo8mJUEc1jx1avMLY9JRTMJMD";
+    const INNER: &str = "QmNLei78zWmzUdbeRB3CiUfAizWUrbeeZh5K1rhAQKCh51";
+    const ASSET: &str = "QmSCRCPFznxEX6S316M4yVmxdxPB6XN63ob2LjFYkP6MLq";
+
+    fn outer_method(_ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
+        crate::call(INNER, "inner", &[1, 2, 3])?;
+        Ok(PackedValue::default())
+    }
+
+    fn inner_method(_ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
+        Ok(PackedValue::default())
+    }
+
+    #[derive(Deserialize)]
+    struct StoreArgs {
+        key: String,
+        value: u64,
+    }
+
+    fn check_store_args(args: &StoreArgs) -> WasmResult<()> {
+        match args.value {
+            0 => Err(WasmError::new("zero value")),
+            _ => Ok(()),
+        }
+    }
+
+    fn store_method(_ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue> {
+        let args: StoreArgs = validate_args!(args, check_store_args);
+        crate::store_data(&args.key, &rmp_serialize(&args.value)?);
+        Ok(PackedValue::default())
+    }
+
+    fn state_checksum() -> Vec<u8> {
+        Sha256::digest(serialize_state()).to_vec()
+    }
+
+    #[test]
+    fn nested_call_trace() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(OUTER, "outer", outer_method);
+        set_contract_method(INNER, "inner", inner_method);
+
+        crate::call(OUTER, "outer", &[0xab; 20]).unwrap();
+
+        let trace = format_call_trace();
+        let expected = format!(
+            "{}::outer({}..)\n  {}::inner(010203)\n",
+            OUTER,
+            "ab".repeat(TRACE_ARGS_MAX),
+            INNER
+        );
+        assert_eq!(trace, expected);
+        assert_eq!(get_call_trace()[1].depth, 2);
+    }
+
+    #[test]
+    fn seeded_withdraw_lock_blocks_transfer() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(ASSET, "transfer", asset_transfer);
+        seed_locked_asset(OWNER, ASSET, 10, LockPrivilege::Creator, LockType::Withdraw);
+
+        let res = crate::asset_transfer(OWNER, CALLER, ASSET, 5);
+
+        assert_eq!(res.unwrap_err().to_string(), "source account locked");
+        assert_asset_lock(
+            OWNER,
+            ASSET,
+            Some((LockPrivilege::Creator, LockType::Withdraw)),
+        );
+        assert_asset_lock(CALLER, ASSET, None);
+    }
+
+    #[test]
+    fn mock_host_builder() {
+        let pk = PublicKey::Ecdsa(crate::ecdsa::PublicKey {
+            curve_id: crate::ecdsa::CurveId::Secp384R1,
+            value: vec![4, 1, 2, 3],
+        });
+
+        MockHost::new().drand(7).verify(false).timestamp(42).build();
+
+        assert_eq!(crate::drand(100), 7);
+        assert_eq!(crate::drand(5), 4);
+        assert!(!crate::verify(&pk, &[1, 2, 3], &[1]));
+        assert_eq!(crate::get_block_time(), 42);
+    }
+
+    #[test]
+    fn mock_host_builder_defaults() {
+        MockHost::new().drand(7).build();
+        MockHost::new().build();
+
+        assert_eq!(crate::drand(100), 50);
+        assert_eq!(crate::get_block_time(), 1652780598);
+    }
+
+    #[test]
+    fn state_snapshot_roundtrip() {
+        set_account_data(OWNER, "key", &[1, 2, 3]);
+        set_account_asset_gen(CALLER, ASSET, 42u64);
+        set_account_contract(OUTER, vec![0xab; 4]);
+        let buf = serialize_state();
+        set_account_data(OWNER, "key", &[]);
+        set_account_data(OWNER, "other", &[4]);
+        remove_account_asset(CALLER, ASSET);
+        set_account_contract(OUTER, vec![]);
+
+        deserialize_state(&buf).unwrap();
+
+        assert_eq!(get_account_data(OWNER, "key"), vec![1, 2, 3]);
+        assert!(get_account_data(OWNER, "other").is_empty());
+        assert_eq!(get_account_asset_gen::<u64>(CALLER, ASSET), 42);
+        assert_eq!(get_account_contract(OUTER), vec![0xab; 4]);
+        assert_eq!(serialize_state(), buf);
+    }
+
+    #[test]
+    fn validate_args_no_mutation() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_account_data(OWNER, "existing", &[1]);
+        let checksum = state_checksum();
+        let malformed = PackedValue(vec![0xc1]);
+        let invalid = PackedValue(rmp_serialize(&("key", 0u64)).unwrap());
+
+        let malformed_res = store_method(create_app_context(OWNER, CALLER), malformed);
+        let invalid_res = store_method(create_app_context(OWNER, CALLER), invalid);
+
+        assert!(malformed_res.is_err());
+        assert_eq!(invalid_res.unwrap_err().to_string(), "zero value");
+        assert_eq!(state_checksum(), checksum);
+    }
+
+    fn lock_and_transfer(account: &str, lock_type: LockType) -> WasmResult<()> {
+        set_contract_method(ASSET, "transfer", asset_transfer);
+        set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+        set_account_asset_gen(CALLER, ASSET, Asset::new(10));
+        seed_locked_asset(account, ASSET, 10, LockPrivilege::Owner, lock_type);
+        crate::asset_transfer(OWNER, CALLER, ASSET, 5)
+    }
+
+    #[test]
+    fn source_deposit_lock_allows_transfer() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+
+        let res = lock_and_transfer(OWNER, LockType::Deposit);
+
+        assert!(res.is_ok());
+        assert_eq!(get_account_asset_gen::<Asset>(CALLER, ASSET).units, 15);
+    }
+
+    #[test]
+    fn source_full_lock_blocks_transfer() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+
+        let res = lock_and_transfer(OWNER, LockType::Full);
+
+        assert_eq!(res.unwrap_err().to_string(), "source account locked");
+    }
+
+    #[test]
+    fn destination_withdraw_lock_allows_transfer() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+
+        let res = lock_and_transfer(CALLER, LockType::Withdraw);
+
+        assert!(res.is_ok());
+        assert_eq!(get_account_asset_gen::<Asset>(OWNER, ASSET).units, 5);
+    }
+
+    #[test]
+    fn destination_deposit_lock_blocks_transfer() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+
+        let res = lock_and_transfer(CALLER, LockType::Deposit);
+
+        assert_eq!(res.unwrap_err().to_string(), "destination account locked");
+        assert_eq!(get_account_asset_gen::<Asset>(OWNER, ASSET).units, 10);
+    }
+
+    #[test]
+    fn destination_full_lock_blocks_transfer() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+
+        let res = lock_and_transfer(CALLER, LockType::Full);
+
+        assert_eq!(res.unwrap_err().to_string(), "destination account locked");
+    }
+
+    fn panicking_method(_ctx: AppContext, args: PackedValue) -> WasmResult<PackedValue> {
+        let _value: u64 = rmp_deserialize(&args).unwrap();
+        Ok(PackedValue::default())
+    }
+
+    #[test]
+    fn smoke_test_report() {
+        set_contract_method(OUTER, "tolerant", inner_method);
+        set_contract_method(OUTER, "strict", store_method);
+        set_contract_method(OUTER, "panicking", panicking_method);
+        set_contract_method(INNER, "other", store_method);
+
+        let report = smoke_test_account(OUTER, &Value::Map(Default::default()));
+
+        assert_eq!(report.succeeded, vec!["tolerant"]);
+        assert_eq!(
+            report.failed,
+            vec![
+                ("panicking".to_owned(), "panicked".to_owned()),
+                ("strict".to_owned(), "deserialization failure".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn drand_sequence_in_order() {
+        let draws = with_drand_sequence(&[3, 1, 200], || {
+            (0..3).map(|_| crate::drand(100)).collect::<Vec<_>>()
+        });
+
+        assert_eq!(draws, vec![3, 1, 99]);
+        assert_eq!(crate::drand(100), 50);
+    }
+
+    #[test]
+    fn drand_sequence_scoped_restores_previous() {
+        set_drand_sequence(vec![7, 8]);
+
+        let draws = with_drand_sequence(&[1], || crate::drand(100));
+
+        assert_eq!(draws, 1);
+        assert_eq!(crate::drand(100), 7);
+        assert_eq!(crate::drand(100), 8);
+        assert_eq!(crate::drand(100), 50);
+    }
+
+    #[test]
+    fn transfer_memo_size() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(ASSET, "transfer", asset_transfer);
+        set_account_asset_gen(OWNER, ASSET, Asset::new(10));
+        set_memo_size_max(8);
+
+        let large = crate::adv_asset_transfer(OWNER, CALLER, ASSET, 1, &[0; 9]);
+        let fitting = crate::adv_asset_transfer(OWNER, CALLER, ASSET, 1, &[0; 8]);
+
+        assert_eq!(large.unwrap_err().to_string(), "memo too large");
+        assert!(fitting.is_ok());
+        assert_eq!(get_account_asset_gen::<Asset>(OWNER, ASSET).units, 9);
+    }
+
+    #[test]
+    fn custom_network_params() {
+        let params = NetworkParams {
+            fuel_asset: ASSET.to_owned(),
+        };
+        set_network_params("testnet", params.clone());
+
+        let custom = network_params("testnet");
+        let default = network_params("skynet");
+
+        assert_eq!(custom, params);
+        assert_eq!(default.fuel_asset, DEFAULT_FUEL_ASSET);
+    }
+
+    #[test]
+    fn account_state_root_stable() {
+        set_account_data(OWNER, "a", &[1]);
+        set_account_data(OWNER, "b", &[2]);
+        set_account_asset(OWNER, ASSET, &[3]);
+        let root = account_state_root(OWNER);
+        set_account_data(OWNER, "a", &[]);
+        set_account_data(OWNER, "a", &[1]);
+
+        let reordered = account_state_root(OWNER);
+        set_account_data(OWNER, "b", &[4]);
+        let data_changed = account_state_root(OWNER);
+        set_account_data(OWNER, "b", &[2]);
+        set_account_asset(OWNER, ASSET, &[5]);
+        let asset_changed = account_state_root(OWNER);
+        set_account_asset(OWNER, ASSET, &[3]);
+        set_account_contract(OWNER, vec![6]);
+        let contract_changed = account_state_root(OWNER);
+
+        assert_eq!(root, reordered);
+        assert_ne!(root, data_changed);
+        assert_ne!(root, asset_changed);
+        assert_ne!(root, contract_changed);
+    }
+
+    #[test]
+    fn account_state_root_unknown_account() {
+        set_account_data(OWNER, "a", &[1]);
+
+        let root = account_state_root(INNER);
+
+        assert_ne!(root, account_state_root(OWNER));
+        assert!(!list_accounts().contains(&INNER.to_string()));
+    }
+
+    fn emit_test_events() {
+        crate::emit_data("created", &rmp_serialize(&42u64).unwrap());
+        crate::emit_data("moved", &rmp_serialize(&("a", "b")).unwrap());
+    }
+
+    #[test]
+    fn assert_emitted_events() {
+        emit_test_events();
+
+        assert_events(&[
+            ("created", Value::U64(42)),
+            (
+                "moved",
+                Value::Seq(vec![Value::String("a".into()), Value::String("b".into())]),
+            ),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "emitted events mismatch at index 1")]
+    fn assert_emitted_events_mismatch() {
+        emit_test_events();
+
+        assert_events(&[("created", Value::U64(42)), ("moved", Value::U64(0))]);
+    }
+
+    #[test]
+    fn get_keys_invalid_utf8_pattern() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        let pattern_addr = write_mem(&[240, 159, 146, b'*']);
+
+        let wslice = hf_get_keys(pattern_addr, 4);
+
+        let output: AppOutput = rmp_deserialize(slice_from_wslice(wslice)).unwrap();
+        assert!(!output.success);
+        assert_eq!(output.data, b"invalid utf-8 string");
+    }
+
+    #[test]
+    fn store_load_data_invalid_utf8_key() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_account_data(OWNER, "\u{fffd}", &[9]);
+        let key_addr = write_mem(&[240, 159, 146]);
+        let data_addr = write_mem(&[1, 2, 3]);
+
+        hf_store_data(key_addr, 3, data_addr, 3);
+        let wslice = hf_load_data(key_addr, 3);
+
+        assert!(slice_from_wslice(wslice).is_empty());
+        assert_eq!(get_account_data(OWNER, "\u{fffd}"), vec![9]);
+        assert_eq!(get_account_keys(OWNER), vec!["\u{fffd}".to_string()]);
+    }
+
+    fn ping_method(_ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
+        crate::call(INNER, "pong", &[])?;
+        Ok(PackedValue::default())
+    }
+
+    fn pong_method(_ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
+        crate::call(OUTER, "ping", &[])?;
+        Ok(PackedValue::default())
+    }
+
+    #[test]
+    fn call_cycle_detection() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(OUTER, "ping", ping_method);
+        set_contract_method(INNER, "pong", pong_method);
+        set_cycle_detection(true);
+
+        let res = crate::call(OUTER, "ping", &[]);
+
+        assert_eq!(res.unwrap_err().to_string(), "call cycle detected");
+        assert_eq!(get_call_trace().len(), 3);
+        assert!(thread_data().borrow().call_stack.is_empty());
+    }
+
+    #[test]
+    fn repeated_calls_are_not_cycles() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(INNER, "inner", inner_method);
+        set_cycle_detection(true);
+
+        let first = crate::call(INNER, "inner", &[]);
+        let second = crate::call(INNER, "inner", &[]);
+
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+    }
+
+    meta_method!("test-contract", "0.1.0"; inner_method, __meta);
+
+    #[test]
+    fn meta_method_lists_exported_methods() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(INNER, "__meta", __meta);
+
+        let buf = crate::call(INNER, "__meta", &[]).unwrap();
+
+        let meta: ContractMeta = rmp_deserialize(&buf).unwrap();
+        assert_eq!(
+            meta,
+            ContractMeta {
+                name: "test-contract".to_string(),
+                version: "0.1.0".to_string(),
+                methods: vec!["inner_method".to_string(), "__meta".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn mint_up_to_max_supply() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(ASSET, "mint", asset_mint);
+        set_asset_max_supply(ASSET, 100);
+        let mint = |units| {
+            let args = rmp_serialize(&AssetMintArgs { to: CALLER, units }).unwrap();
+            crate::call(ASSET, "mint", &args)
+        };
+
+        let first = mint(60);
+        let to_cap = mint(40);
+        let beyond = mint(1);
+
+        assert!(first.is_ok());
+        assert!(to_cap.is_ok());
+        assert_eq!(beyond.unwrap_err().to_string(), "max supply exceeded");
+        assert_eq!(get_account_asset_gen::<Asset>(CALLER, ASSET).units, 100);
+    }
+
+    #[test]
+    fn max_call_depth_reached() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(OUTER, "outer", outer_method);
+        set_contract_method(INNER, "inner", inner_method);
+
+        crate::call(OUTER, "outer", &[]).unwrap();
+        let reached = get_max_call_depth_reached();
+        reset_max_call_depth_reached();
+        crate::call(INNER, "inner", &[]).unwrap();
+
+        assert_eq!(reached, 2);
+        assert_eq!(get_max_call_depth_reached(), 1);
+    }
+
+    #[test]
+    fn try_load_asset_typed_empty() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+
+        let asset = crate::try_load_asset_typed::<Asset>(ASSET).unwrap();
+
+        assert_eq!(asset, None);
+    }
+
+    #[test]
+    fn try_load_asset_typed_valid() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_account_asset_gen(OWNER, ASSET, Asset::new(0));
+
+        let asset = crate::try_load_asset_typed::<Asset>(ASSET).unwrap();
+
+        assert_eq!(asset, Some(Asset::new(0)));
+    }
+
+    #[test]
+    fn try_load_asset_typed_corrupt() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_account_asset(OWNER, ASSET, &[0xc1]);
+
+        let err = crate::try_load_asset_typed::<Asset>(ASSET).unwrap_err();
+
+        assert_eq!(err.to_string(), "deserialization failure");
+    }
+
+    #[test]
+    fn store_and_load_data_typed() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        let value = (42u64, "foo".to_string());
+
+        crate::store_data_typed("pair", &value).unwrap();
+        let loaded: (u64, String) = crate::load_data_typed("pair").unwrap();
+
+        assert_eq!(loaded, value);
+    }
+
+    #[test]
+    fn load_data_typed_absent_key() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+
+        let err = crate::load_data_typed::<u64>("missing").unwrap_err();
+
+        assert_eq!(err.to_string(), "data not found");
+    }
+
+    #[test]
+    fn emitted_events_order() {
+        emit_test_events();
+
+        let events = get_emitted_events();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0, "created");
+        assert_eq!(events[0].1, rmp_serialize(&42u64).unwrap());
+        assert_eq!(events[1].0, "moved");
+        assert_eq!(events[1].1, rmp_serialize(&("a", "b")).unwrap());
+    }
+
+    #[test]
+    fn clear_events() {
+        emit_test_events();
+
+        clear_emitted_events();
+        crate::emit_data("created", &[]);
+
+        let events = get_emitted_events();
+        assert_eq!(events, vec![("created".to_string(), vec![])]);
+    }
+
+    fn lottery_method(_ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
+        let outcome = match crate::drand(100) < 10 {
+            true => "win",
+            false => "lose",
+        };
+        rmp_serialize(&outcome).map(PackedValue)
+    }
+
+    #[test]
+    fn drand_sequence_branches() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(OUTER, "draw", lottery_method);
+        set_drand_sequence(vec![3, 99]);
+
+        let low = crate::call(OUTER, "draw", &[]).unwrap();
+        let high = crate::call(OUTER, "draw", &[]).unwrap();
+
+        assert_eq!(rmp_deserialize::<&str>(&low).unwrap(), "win");
+        assert_eq!(rmp_deserialize::<&str>(&high).unwrap(), "lose");
+    }
+
+    #[test]
+    fn drand_sequence_exhausted() {
+        set_drand_sequence(vec![500]);
+
+        let clamped = crate::drand(100);
+        let fallback = crate::drand(100);
+
+        assert_eq!(clamped, 99);
+        assert_eq!(fallback, 50);
+    }
+
+    #[test]
+    fn verify_result_accepted() {
+        let pk = PublicKey::ecdsa(crate::ecdsa::CurveId::Secp384R1, vec![4, 1, 2, 3]);
+        set_verify_result(true);
+
+        let res = crate::verify(&pk, &[1, 2, 3], &[0]);
+
+        assert!(res);
+    }
+
+    #[test]
+    fn verify_result_rejected() {
+        let pk = PublicKey::ecdsa(crate::ecdsa::CurveId::Secp384R1, vec![4, 1, 2, 3]);
+        set_verify_result(false);
+
+        let res = crate::verify(&pk, &[1, 2, 3], &[1]);
+
+        assert!(!res);
+    }
+
+    #[test]
+    fn verify_result_default() {
+        let pk = PublicKey::ecdsa(crate::ecdsa::CurveId::Secp384R1, vec![4, 1, 2, 3]);
+
+        assert!(crate::verify(&pk, &[1, 2, 3], &[1]));
+        assert!(!crate::verify(&pk, &[1, 2, 3], &[0]));
+    }
+
+    #[test]
+    fn storage_size() {
+        set_account_data(OWNER, "foo", &[1, 2, 3]);
+        set_account_data(OWNER, "bar", &[4; 10]);
+        set_account_asset_gen(OWNER, ASSET, Asset::new(42));
+        set_account_data(CALLER, "foo", &[5; 7]);
+
+        let size = account_storage_size(OWNER);
+
+        let asset_len = rmp_serialize(&Asset::new(42)).unwrap().len();
+        assert_eq!(size, 3 + 10 + asset_len);
+        assert_eq!(account_storage_size(INNER), 0);
+        assert!(!list_accounts().contains(&INNER.to_string()));
+    }
+
+    #[test]
+    fn is_callable_registered_method() {
+        set_contract_method(INNER, "inner", inner_method);
+
+        assert!(crate::is_callable(INNER, "inner"));
+        assert!(!crate::is_callable(INNER, "outer"));
+        assert!(!crate::is_callable(OUTER, "inner"));
+    }
+
+    #[test]
+    fn list_created_accounts() {
+        set_account_data(OWNER, "foo", &[1]);
+        set_account_asset_gen(CALLER, ASSET, Asset::new(1));
+
+        let accounts = list_accounts();
+
+        let mut expected = vec![OWNER.to_string(), CALLER.to_string()];
+        expected.sort();
+        assert_eq!(accounts, expected);
+    }
+
+    fn direct_only_method(ctx: AppContext, _args: PackedValue) -> WasmResult<PackedValue> {
+        match ctx.origin == ctx.caller {
+            true => Ok(PackedValue::default()),
+            false => Err(WasmError::new("intermediary not allowed")),
+        }
+    }
+
+    #[test]
+    fn app_context_full_fields() {
+        let ctx = create_app_context_full(OWNER, CALLER, INNER, "direct", "testnet", 2);
+
+        assert_eq!(ctx.owner, OWNER);
+        assert_eq!(ctx.caller, CALLER);
+        assert_eq!(ctx.origin, INNER);
+        assert_eq!(ctx.method, "direct");
+        assert_eq!(ctx.network, "testnet");
+        assert_eq!(ctx.depth, 2);
+    }
+
+    #[test]
+    fn app_context_full_origin_differs() {
+        let direct = create_app_context_full(OWNER, CALLER, CALLER, "direct", "skynet", 0);
+        let relayed = create_app_context_full(OWNER, CALLER, INNER, "direct", "skynet", 1);
+
+        let direct = call_wrap(direct_only_method, direct, PackedValue::default());
+        let relayed = call_wrap(direct_only_method, relayed, PackedValue::default());
+
+        assert!(direct.is_ok());
+        assert_eq!(relayed.unwrap_err().to_string(), "intermediary not allowed");
+    }
+
+    abi!(inner_method, __meta);
+
+    #[test]
+    fn abi_lists_exported_methods() {
+        let entries = abi();
+
+        let methods: Vec<&str> = entries.iter().map(|entry| entry.method.as_str()).collect();
+        assert_eq!(methods, ["inner_method", "__meta"]);
+        assert!(entries.iter().all(|entry| entry.doc.is_empty()));
+    }
+
+    state_method!();
+
+    #[test]
+    fn state_method_call() {
+        let ctx = create_app_context(OWNER, CALLER);
+        set_app_ctx(&ctx);
+        set_contract_method(INNER, "__state", __state);
+        set_account_data(INNER, "name", &rmp_serialize(&"foo").unwrap());
+
+        let buf = crate::call(INNER, "__state", &[]).unwrap();
+
+        let state: Value = rmp_deserialize(&buf).unwrap();
+        assert_eq!(get_value_as_str!(state, "name").unwrap(), "foo");
+    }
+}
@@ -17,7 +17,7 @@
 
 //! Trinci Applications Interface (TAI).
 
-use crate::PackedValue;
+use crate::{PackedValue, WasmError, WasmResult};
 use serde::{Deserialize, Serialize};
 
 /// Asset's Lock Level.
@@ -77,6 +77,41 @@ impl Asset {
     }
 }
 
+/// Compose an amount in base units from its whole and fractional parts.
+///
+/// The `fraction` is expressed in units of `10^-decimals`, e.g. with 9
+/// decimals the human amount `1.5` is given as `whole = 1` and
+/// `fraction = 500_000_000`.
+pub fn scale_amount(whole: u64, fraction: u64, decimals: u8) -> WasmResult<u64> {
+    let scale = 10u64
+        .checked_pow(decimals as u32)
+        .ok_or_else(|| WasmError::new("decimals out of range"))?;
+    if fraction >= scale {
+        return Err(WasmError::new("fraction exceeds decimals precision"));
+    }
+    whole
+        .checked_mul(scale)
+        .and_then(|units| units.checked_add(fraction))
+        .ok_or_else(|| WasmError::new("amount overflow"))
+}
+
+/// Order two asset ids canonically (lexicographically).
+pub fn canonical_pair<'a>(asset_a: &'a str, asset_b: &'a str) -> (&'a str, &'a str) {
+    match asset_a <= asset_b {
+        true => (asset_a, asset_b),
+        false => (asset_b, asset_a),
+    }
+}
+
+/// Key identifying the pool of an asset pair, independent of the arguments order.
+pub fn pool_key(asset_a: &str, asset_b: &str) -> WasmResult<String> {
+    if asset_a == asset_b {
+        return Err(WasmError::new("identical pool assets"));
+    }
+    let (first, second) = canonical_pair(asset_a, asset_b);
+    Ok(format!("{}:{}", first, second))
+}
+
 /// Arguments for asset `lock` method.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 pub struct AssetLockArgs<'a> {
@@ -95,6 +130,13 @@ pub struct AssetTransferArgs<'a> {
     pub data: Option<Vec<u8>>,
 }
 
+/// Arguments for the asset `mint` method.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct AssetMintArgs<'a> {
+    pub to: &'a str,
+    pub units: u64,
+}
+
 /// Arguments for the asset `balance` method.
 pub type AssetBalanceArgs = PackedValue;
 
@@ -233,4 +275,52 @@ mod tests {
 
         assert_eq!(args, expected);
     }
+
+    #[test]
+    fn scale_amount_with_decimals() {
+        let units = scale_amount(1, 500_000_000, 9).unwrap();
+
+        assert_eq!(units, 1_500_000_000);
+    }
+
+    #[test]
+    fn scale_amount_zero_decimals() {
+        assert_eq!(scale_amount(42, 0, 0).unwrap(), 42);
+        assert!(scale_amount(42, 1, 0).is_err());
+    }
+
+    #[test]
+    fn scale_amount_over_precise_fraction() {
+        let err = scale_amount(1, 1_000_000_000, 9).unwrap_err();
+
+        assert_eq!(err.to_string(), "fraction exceeds decimals precision");
+    }
+
+    #[test]
+    fn scale_amount_overflow() {
+        let err = scale_amount(u64::MAX, 0, 1).unwrap_err();
+
+        assert_eq!(err.to_string(), "amount overflow");
+    }
+
+    #[test]
+    fn pool_key_order_independent() {
+        let asset_a = "QmTeNPcQnoxinb9bcQhuFxteTQ4sN3qSWJNoHjgEr84zNY";
+        let asset_b = "QmZKrfoq8ZtkH445373qFQo8mJUEc1jx1avMLY9JRTMJMD";
+
+        let key_ab = pool_key(asset_a, asset_b).unwrap();
+        let key_ba = pool_key(asset_b, asset_a).unwrap();
+
+        assert_eq!(key_ab, key_ba);
+        assert_eq!(key_ab, format!("{}:{}", asset_a, asset_b));
+    }
+
+    #[test]
+    fn pool_key_identical_assets() {
+        let asset = "QmTeNPcQnoxinb9bcQhuFxteTQ4sN3qSWJNoHjgEr84zNY";
+
+        let err = pool_key(asset, asset).unwrap_err();
+
+        assert_eq!(err.to_string(), "identical pool assets");
+    }
 }
@@ -17,8 +17,90 @@
 
 //! Serde Value rappresentation
 //!
+//! Helpers to extract structured data from a `Value`.
+//!
 //! TODO: these tests shall be in the serde-value crate.
 
+use crate::common::{rmp_serialize, WasmError, WasmResult};
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+/// Get a vector of strings from a `Value::Seq`.
+///
+/// Fails if the value is not a sequence or if any element is not a string.
+pub fn as_str_vec(value: &Value) -> WasmResult<Vec<String>> {
+    let seq = match value {
+        Value::Seq(seq) => seq,
+        _ => return Err(WasmError::new("value is not an array")),
+    };
+    seq.iter()
+        .enumerate()
+        .map(|(i, elem)| match elem {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(WasmError::new(&format!("element {} is not a string", i))),
+        })
+        .collect()
+}
+
+/// Normalize a `Value` so that semantically-equal values are also equal.
+///
+/// Integers are converted to `U64`, or `I64` when negative, floats to `F64`,
+/// chars to strings, options and newtypes are unwrapped. Maps and sequences
+/// are normalized recursively, map keys are thus sorted by their normalized
+/// value. Fails if two keys of a map have the same normalized value.
+pub fn normalize(value: &Value) -> WasmResult<Value> {
+    fn int(val: i64) -> Value {
+        match val >= 0 {
+            true => Value::U64(val as u64),
+            false => Value::I64(val),
+        }
+    }
+
+    let res = match value {
+        Value::U8(val) => Value::U64(*val as u64),
+        Value::U16(val) => Value::U64(*val as u64),
+        Value::U32(val) => Value::U64(*val as u64),
+        Value::I8(val) => int(*val as i64),
+        Value::I16(val) => int(*val as i64),
+        Value::I32(val) => int(*val as i64),
+        Value::I64(val) => int(*val),
+        Value::F32(val) => Value::F64(*val as f64),
+        Value::Char(val) => Value::String(val.to_string()),
+        Value::Option(None) => Value::Unit,
+        Value::Option(Some(val)) | Value::Newtype(val) => return normalize(val),
+        Value::Seq(seq) => Value::Seq(seq.iter().map(normalize).collect::<WasmResult<_>>()?),
+        Value::Map(map) => {
+            let mut normalized = BTreeMap::new();
+            for (key, val) in map {
+                if normalized
+                    .insert(normalize(key)?, normalize(val)?)
+                    .is_some()
+                {
+                    return Err(WasmError::new("duplicate normalized map key"));
+                }
+            }
+            Value::Map(normalized)
+        }
+        val => val.clone(),
+    };
+    Ok(res)
+}
+
+/// Serialize the normalized `Value`, see `normalize`.
+///
+/// Any two semantically-equal values produce the same bytes, thus the result
+/// is suitable for content hashing.
+pub fn canonical_bytes(value: &Value) -> WasmResult<Vec<u8>> {
+    rmp_serialize(&normalize(value)?)
+}
+
+/// Deterministic key string for the `Value`, usable as data key.
+///
+/// The key is the base58 encoding of the value canonical bytes, see `canonical_bytes`.
+pub fn to_key(value: &Value) -> WasmResult<String> {
+    canonical_bytes(value).map(|buf| bs58::encode(buf).into_string())
+}
+
 #[cfg(test)]
 mod value_serialize_tests {
     use crate::common::rmp_serialize;
@@ -350,3 +432,196 @@ mod value_deserialize_tests {
         assert_eq!(val, val_des);
     }
 }
+
+#[cfg(test)]
+mod value_helpers_tests {
+    use super::*;
+    use crate::value;
+
+    #[test]
+    fn str_vec_from_seq() {
+        let val = value!(["foo", "bar"]);
+
+        let res = as_str_vec(&val).unwrap();
+
+        assert_eq!(res, vec!["foo".to_string(), "bar".to_string()]);
+    }
+
+    #[test]
+    fn str_vec_with_non_string_element() {
+        let val = value!(["foo", 3]);
+
+        let err = as_str_vec(&val).unwrap_err();
+
+        assert_eq!(err.to_string(), "element 1 is not a string");
+    }
+
+    #[test]
+    fn str_vec_from_map_field() {
+        let args = value!({
+            "ids": ["foo", "bar"],
+            "bad": ["foo", false],
+        });
+
+        let ids = get_value_as_str_vec!(args, "ids").unwrap();
+        let err = get_value_as_str_vec!(args, "bad").unwrap_err();
+
+        assert_eq!(ids, vec!["foo".to_string(), "bar".to_string()]);
+        assert_eq!(err.to_string(), "`args::bad` has bad type");
+    }
+
+    #[test]
+    fn positional_args_binding() {
+        let args = value!(["foo", "bar", 42]);
+
+        let (from, to, units) = positional_args!(args, str, str, u64).unwrap();
+
+        assert_eq!((from, to, units), ("foo", "bar", 42));
+    }
+
+    #[test]
+    fn positional_args_bad_arity() {
+        let args = value!(["foo", "bar"]);
+
+        let err = positional_args!(args, str, str, u64).unwrap_err();
+
+        assert_eq!(err.to_string(), "`args` has bad arity");
+    }
+
+    #[test]
+    fn positional_args_bad_type() {
+        let args = value!(["foo", 3, 42]);
+
+        let err = positional_args!(args, str, str, u64).unwrap_err();
+
+        assert_eq!(err.to_string(), "`args::1` has bad type");
+    }
+
+    #[test]
+    fn positional_args_not_array() {
+        let args = value!({ "from": "foo" });
+
+        let err = positional_args!(args, str).unwrap_err();
+
+        assert_eq!(err.to_string(), "`args` is not an array");
+    }
+
+    #[test]
+    fn normalize_integers() {
+        assert_eq!(normalize(&Value::I32(1)).unwrap(), Value::U64(1));
+        assert_eq!(normalize(&Value::I8(-1)).unwrap(), Value::I64(-1));
+    }
+
+    #[test]
+    fn normalize_floats() {
+        assert_eq!(normalize(&Value::F32(0.5)).unwrap(), Value::F64(0.5));
+        assert_eq!(
+            canonical_bytes(&Value::F32(0.5)).unwrap(),
+            canonical_bytes(&Value::F64(0.5)).unwrap()
+        );
+    }
+
+    #[test]
+    fn normalize_duplicate_map_keys() {
+        let map = Value::Map(
+            vec![(Value::U8(1), Value::U8(1)), (Value::I32(1), Value::U8(2))]
+                .into_iter()
+                .collect(),
+        );
+
+        let err = canonical_bytes(&map).unwrap_err();
+
+        assert_eq!(err.to_string(), "duplicate normalized map key");
+    }
+
+    #[test]
+    fn canonical_bytes_integer_variants() {
+        let signed = canonical_bytes(&Value::I32(1)).unwrap();
+        let unsigned = canonical_bytes(&Value::U8(1)).unwrap();
+
+        assert_eq!(signed, unsigned);
+    }
+
+    #[test]
+    fn canonical_bytes_map_keys() {
+        let map_a = Value::Map(
+            vec![
+                (Value::I32(2), Value::U8(1)),
+                (Value::U16(1), Value::I64(1)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let map_b = Value::Map(
+            vec![(Value::U8(1), Value::U32(1)), (Value::U64(2), Value::I8(1))]
+                .into_iter()
+                .collect(),
+        );
+
+        let bytes_a = canonical_bytes(&map_a).unwrap();
+        let bytes_b = canonical_bytes(&map_b).unwrap();
+
+        assert_eq!(bytes_a, bytes_b);
+    }
+
+    #[test]
+    fn to_key_equal_values() {
+        let pool = Value::String("pool".to_string());
+        let key_a = to_key(&Value::Seq(vec![pool.clone(), Value::U8(1), Value::U16(2)])).unwrap();
+        let key_b = to_key(&Value::Seq(vec![pool, Value::U64(1), Value::I32(2)])).unwrap();
+
+        assert_eq!(key_a, key_b);
+    }
+
+    #[test]
+    fn to_key_different_values() {
+        let key_a = to_key(&value!(["ab", "c"])).unwrap();
+        let key_b = to_key(&value!(["a", "bc"])).unwrap();
+        let key_c = to_key(&value!("abc")).unwrap();
+
+        assert_ne!(key_a, key_b);
+        assert_ne!(key_a, key_c);
+        assert_ne!(key_b, key_c);
+    }
+
+    #[test]
+    fn get_value_as_scalars() {
+        let val = value!({
+            "delta": -5,
+            "enabled": true,
+            "ratio": 0.5,
+        });
+
+        assert_eq!(get_value_as_i64!(val, "delta").unwrap(), -5);
+        assert!(get_value_as_bool!(val, "enabled").unwrap());
+        assert_eq!(get_value_as_f64!(val, "ratio").unwrap(), 0.5);
+    }
+
+    #[test]
+    fn get_value_as_bytes_field() {
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(value!("data"), Value::Bytes(vec![1, 2, 3]));
+        let val = Value::Map(map);
+
+        let data = get_value_as_bytes!(val, "data").unwrap();
+
+        assert_eq!(data.to_vec(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn get_value_as_scalars_bad_type() {
+        let val = value!({ "name": "foo" });
+
+        let i64_err = get_value_as_i64!(val, "name").unwrap_err();
+        let bool_err = get_value_as_bool!(val, "name").unwrap_err();
+        let f64_err = get_value_as_f64!(val, "name").unwrap_err();
+        let bytes_err = get_value_as_bytes!(val, "name").unwrap_err();
+        let missing = get_value_as_bool!(val, "other").unwrap_err();
+
+        assert_eq!(i64_err.to_string(), "`val::name` has bad type");
+        assert_eq!(bool_err.to_string(), "`val::name` has bad type");
+        assert_eq!(f64_err.to_string(), "`val::name` has bad type");
+        assert_eq!(bytes_err.to_string(), "`val::name` has bad type");
+        assert_eq!(missing.to_string(), "`val::other` not found");
+    }
+}